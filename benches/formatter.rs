@@ -0,0 +1,71 @@
+// Stream a ~20k tokens answer to the chat and measure the formatting time.
+// Run with `cargo bench --bench formatter`
+
+use std::time::Instant;
+
+use tenere::{
+    chat::Chat, config::MarkdownRenderer, formatter::Formatter, llm::LLMAnswer, theme::Theme,
+};
+
+// markdown answer of about `tokens` tokens, a token being ~4 chars
+fn answer(tokens: usize) -> String {
+    let section = r#"## Section
+
+Some text with **bold**, `inline code` and a [link](https://example.com).
+The paragraph continues on a second line to make it a bit longer.
+
+- first item
+- second item with `code`
+
+```rust
+fn main() {
+    let numbers: Vec<u32> = (0..10).collect();
+    println!("{:?}", numbers);
+}
+```
+
+"#;
+
+    section.repeat(tokens * 4 / section.len() + 1)
+}
+
+fn main() {
+    let theme = Theme::default();
+    let (formatter_config, formatter_assets) = Formatter::init(&theme);
+    let formatter = Formatter::new(
+        &formatter_config,
+        &formatter_assets,
+        MarkdownRenderer::default(),
+        &theme,
+    );
+
+    let answer = answer(20_000);
+    let tokens: Vec<String> = answer
+        .chars()
+        .collect::<Vec<char>>()
+        .chunks(4)
+        .map(|chunk| chunk.iter().collect())
+        .collect();
+
+    let mut chat = Chat::new();
+
+    let start = Instant::now();
+    chat.handle_answer(LLMAnswer::StartAnswer, &formatter);
+    for token in &tokens {
+        chat.handle_answer(LLMAnswer::Answer(token.clone()), &formatter);
+    }
+    chat.handle_answer(LLMAnswer::EndAnswer, &formatter);
+    let elapsed = start.elapsed();
+
+    println!(
+        "streamed {} tokens ({} bytes) in {:.2?}, {:.2?} per token",
+        tokens.len(),
+        answer.len(),
+        elapsed,
+        elapsed / tokens.len() as u32
+    );
+
+    let start = Instant::now();
+    formatter.format(&answer);
+    println!("formatting the whole answer once: {:.2?}", start.elapsed());
+}
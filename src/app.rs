@@ -1,17 +1,21 @@
+use crate::autosave::Autosave;
+use crate::confirmation::{Confirmation, ConfirmationAction};
 use crate::history::History;
+use crate::llm::LLM;
 use crate::prompt::Prompt;
+use crate::session::Session;
 use crate::{chat::Chat, help::Help};
 use std;
 use std::sync::atomic::AtomicBool;
 
-use crate::notification::Notification;
+use crate::clipboard::Clipboard;
+use crate::notification::{Notification, NotificationLevel};
 use crate::spinner::Spinner;
-use crate::{config::Config, formatter::Formatter};
-use arboard::Clipboard;
-use crossterm::event::KeyCode;
-use ratatui::text::Line;
+use crate::{config::Config, formatter::Formatter, keymap::Key};
+use ratatui::{layout::Rect, text::Line};
 
 use std::sync::Arc;
+use tokio::sync::Mutex;
 
 pub type AppResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
@@ -22,12 +26,22 @@ pub enum FocusedBlock {
     History,
     Preview,
     Help,
+    Confirmation,
 }
 
 pub struct App<'a> {
     pub running: bool,
     pub prompt: Prompt<'a>,
     pub chat: Chat<'a>,
+    pub llm: Arc<Mutex<Box<dyn LLM + 'static>>>,
+    pub model: String,
+    pub temperature: Option<f64>,
+    // id of the session of the active tab
+    pub session: usize,
+    // sessions of the other tabs, the active tab is at the index `tab`
+    pub tabs: Vec<Session<'a>>,
+    pub tab: usize,
+    next_id: usize,
     pub focused_block: FocusedBlock,
     pub history: History<'a>,
     pub notifications: Vec<Notification>,
@@ -35,41 +49,159 @@ pub struct App<'a> {
     pub terminate_response_signal: Arc<AtomicBool>,
     pub clipboard: Option<Clipboard>,
     pub help: Help,
-    pub previous_key: KeyCode,
+    pub confirmation: Option<Confirmation>,
+    pub autosave: Autosave,
+    // start of a key sequence, e.g. `g` of `gg`
+    pub pending_keys: Vec<Key>,
     pub config: Arc<Config>,
     pub formatter: &'a Formatter<'a>,
+    // areas of the last render, to find the block under the mouse
+    pub chat_area: Rect,
+    pub prompt_area: Rect,
 }
 
 impl<'a> App<'a> {
-    pub fn new(config: Arc<Config>, formatter: &'a Formatter<'a>) -> Self {
+    pub fn new(
+        config: Arc<Config>,
+        formatter: &'a Formatter<'a>,
+        llm: Box<dyn LLM + 'static>,
+    ) -> Self {
         Self {
             running: true,
-            prompt: Prompt::default(),
+            prompt: Prompt::new(),
             chat: Chat::new(),
+            llm: Arc::new(Mutex::new(llm)),
+            model: config.model(),
+            temperature: None,
+            session: 0,
+            tabs: Vec::new(),
+            tab: 0,
+            next_id: 1,
             focused_block: FocusedBlock::Prompt,
             history: History::new(),
             notifications: Vec::new(),
             spinner: Spinner::default(),
             terminate_response_signal: Arc::new(AtomicBool::new(false)),
-            clipboard: Clipboard::new().ok(),
-            help: Help::new(),
-            previous_key: KeyCode::Null,
+            clipboard: Clipboard::new(&config.clipboard),
+            help: Help::new(&config.keymap),
+            confirmation: None,
+            autosave: Autosave::new(),
+            pending_keys: Vec::new(),
             config,
             formatter,
+            chat_area: Rect::default(),
+            prompt_area: Rect::default(),
         }
     }
 
+    // move the session of the active tab out of the app
+    fn take_session(&mut self) -> Session<'a> {
+        Session {
+            id: self.session,
+            chat: std::mem::take(&mut self.chat),
+            llm: self.llm.clone(),
+            model: std::mem::take(&mut self.model),
+            temperature: self.temperature,
+            terminate_response_signal: self.terminate_response_signal.clone(),
+            spinner: std::mem::take(&mut self.spinner),
+        }
+    }
+
+    fn load_session(&mut self, session: Session<'a>) {
+        self.session = session.id;
+        self.chat = session.chat;
+        self.llm = session.llm;
+        self.model = session.model;
+        self.temperature = session.temperature;
+        self.terminate_response_signal = session.terminate_response_signal;
+        self.spinner = session.spinner;
+
+        // the edited message belongs to the previous chat
+        self.prompt.editing = None;
+    }
+
+    // unique id of a session or of a stream
+    pub fn new_id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id - 1
+    }
+
+    pub fn tab_count(&self) -> usize {
+        self.tabs.len() + 1
+    }
+
+    // open a new chat in a tab after the active one
+    pub fn new_tab(&mut self, model: String, llm: Box<dyn LLM + 'static>) {
+        let session = self.take_session();
+        self.tabs.insert(self.tab, session);
+        self.tab += 1;
+
+        let session = Session::new(self.new_id(), model, llm);
+        self.load_session(session);
+    }
+
+    pub fn switch_tab(&mut self, tab: usize) {
+        if tab == self.tab || tab >= self.tab_count() {
+            return;
+        }
+
+        let session = self.take_session();
+        self.tabs.insert(self.tab, session);
+
+        let session = self.tabs.remove(tab);
+        self.tab = tab;
+        self.load_session(session);
+    }
+
+    // close the active tab and return its session, the last tab can not be closed
+    pub fn close_tab(&mut self) -> Option<Session<'a>> {
+        if self.tabs.is_empty() {
+            return None;
+        }
+
+        let closed = self.take_session();
+
+        // the tab on the right, or on the left for the last one
+        let tab = self.tab.min(self.tabs.len() - 1);
+        let session = self.tabs.remove(tab);
+        self.tab = tab;
+        self.load_session(session);
+
+        Some(closed)
+    }
+
     pub fn tick(&mut self) {
         self.notifications.retain(|n| n.ttl > 0);
         self.notifications.iter_mut().for_each(|n| n.ttl -= 1);
 
         if self.spinner.active {
-            self.chat.formatted_chat.lines.pop();
             self.chat
-                .formatted_chat
-                .lines
-                .push(Line::raw(format!("🤖: Waiting {}", self.spinner.draw())));
+                .replace_last_line(Line::raw(format!("🤖: Waiting {}", self.spinner.draw())));
             self.spinner.update();
         }
+
+        // the chats of the previous session are kept until they are restored or discarded
+        if self.restore_pending() {
+            return;
+        }
+
+        // the chats of all the tabs, in their order
+        let mut chats: Vec<&Chat> = self.tabs.iter().map(|session| &session.chat).collect();
+        chats.insert(self.tab, &self.chat);
+        if let Err(e) = self.autosave.tick(&chats) {
+            self.notifications
+                .push(Notification::new(e.to_string(), NotificationLevel::Error));
+        }
+    }
+
+    // the user is asked to restore the chats autosaved by the previous session
+    pub fn restore_pending(&self) -> bool {
+        matches!(
+            self.confirmation,
+            Some(Confirmation {
+                action: ConfirmationAction::RestoreChat(_),
+                ..
+            })
+        )
     }
 }
@@ -0,0 +1,345 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    command::{common_prefix, expand_home, Completion},
+    markdown,
+};
+
+/// A file referenced in the prompt with `@path`, `@path:10-40` or a glob like `@src/*.rs`
+#[derive(Debug)]
+struct Attachment {
+    path: PathBuf,
+    // first and last lines, from 1
+    lines: Option<(usize, usize)>,
+    content: String,
+}
+
+impl Attachment {
+    // the content in a fenced block, after the name of the file
+    fn block(&self) -> String {
+        let name = match self.lines {
+            Some((first, last)) => format!("{} (lines {}-{})", self.path.display(), first, last),
+            None => self.path.display().to_string(),
+        };
+        let language = self
+            .path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+
+        format!("{}:\n{}", name, fenced(&self.content, language))
+    }
+}
+
+/// The text in a code block, with a fence longer than the ones of the text
+pub fn fenced(text: &str, language: &str) -> String {
+    let backticks = text
+        .lines()
+        .map(|line| line.trim_start().chars().take_while(|c| *c == '`').count())
+        .max()
+        .unwrap_or_default();
+    let fence = "`".repeat(backticks.max(2) + 1);
+
+    format!(
+        "{}{}\n{}\n{}",
+        fence,
+        language,
+        text.trim_end_matches('\n'),
+        fence
+    )
+}
+
+/// The prompt to send, with the files of its `@path` references
+#[derive(Debug)]
+pub struct Attached {
+    pub text: String,
+    /// Size of the files attached, in bytes
+    pub size: usize,
+    /// The references that could not be attached, they are sent as text,
+    /// and the files of the globs left out
+    pub warnings: Vec<String>,
+}
+
+// `path:10-40` or `path:10`
+fn parse_range(reference: &str) -> (&str, Option<(usize, usize)>) {
+    if let Some((path, range)) = reference.rsplit_once(':') {
+        let lines = match range.split_once('-') {
+            Some((first, last)) => first.parse().ok().zip(last.parse().ok()),
+            None => range.parse().ok().map(|line| (line, line)),
+        };
+        if let Some(lines) = lines {
+            return (path, Some(lines));
+        }
+    }
+    (reference, None)
+}
+
+fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+fn read(path: &Path, lines: Option<(usize, usize)>) -> Result<Attachment, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Can not attach {}: {}", path.display(), e))?;
+
+    let (lines, content) = match lines {
+        Some((first, last)) => {
+            let count = content.lines().count();
+            if first == 0 || first > last || first > count {
+                return Err(format!(
+                    "Lines {}-{} are not in {} ({} lines)",
+                    first,
+                    last,
+                    path.display(),
+                    count
+                ));
+            }
+            let last = last.min(count);
+            let content = content
+                .lines()
+                .skip(first - 1)
+                .take(last - first + 1)
+                .collect::<Vec<_>>()
+                .join("\n");
+            (Some((first, last)), content)
+        }
+        None => (None, content),
+    };
+
+    Ok(Attachment {
+        path: path.to_path_buf(),
+        lines,
+        content,
+    })
+}
+
+// the files of a reference, none when it is not a path, e.g. `@someone`
+fn resolve(path: &str, lines: Option<(usize, usize)>) -> Result<Vec<PathBuf>, String> {
+    if is_glob(path) {
+        let pattern = expand_home(path);
+        let files: Vec<PathBuf> = glob::glob(&pattern.to_string_lossy())
+            .map_err(|e| format!("Invalid pattern `@{}`: {}", path, e))?
+            .filter_map(Result::ok)
+            .filter(|path| path.is_file())
+            .collect();
+
+        if files.is_empty() {
+            return Err(format!("No file matches `@{}`", path));
+        }
+        return Ok(files);
+    }
+
+    let file = expand_home(path);
+    if file.is_dir() {
+        return Err(format!(
+            "`@{}` is a directory, attach its files with `@{}/*`",
+            path,
+            path.trim_end_matches('/')
+        ));
+    }
+    if file.is_file() {
+        return Ok(vec![file]);
+    }
+
+    // a mention rather than a path
+    if lines.is_none() && !path.contains(['/', '.']) {
+        return Ok(Vec::new());
+    }
+    Err(format!("No file `{}`", path))
+}
+
+/// Append the files referenced in the prompt to it, in fenced blocks.
+/// The references in code blocks and code spans are left as they are.
+/// The files of a glob are attached until their size reaches `limit` bytes, they are not read after.
+pub fn attach(prompt: &str, limit: usize) -> Attached {
+    let mut attachments: Vec<Attachment> = Vec::new();
+    let mut warnings: Vec<String> = Vec::new();
+    let mut size = 0;
+
+    let words = markdown::prose(prompt)
+        .into_iter()
+        .flat_map(|range| prompt[range].split_whitespace());
+
+    for word in words {
+        let Some(reference) = word.strip_prefix('@') else {
+            continue;
+        };
+        // the punctuation after the reference, e.g. `@src/main.rs?`
+        let reference = reference.trim_end_matches([',', ';', ':', '!', '?', ')', '"', '\'']);
+        let reference = reference.strip_suffix('.').unwrap_or(reference);
+        if reference.is_empty() {
+            continue;
+        }
+
+        let (path, lines) = parse_range(reference);
+        let mut warn = |warning: String| {
+            if !warnings.contains(&warning) {
+                warnings.push(warning);
+            }
+        };
+
+        let files = match resolve(path, lines) {
+            Ok(files) => files,
+            Err(e) => {
+                warn(format!("{}, it is sent as text", e));
+                continue;
+            }
+        };
+
+        for (index, file) in files.iter().enumerate() {
+            // the same file referenced twice is attached once
+            if attachments
+                .iter()
+                .any(|a| &a.path == file && a.lines == lines)
+            {
+                continue;
+            }
+
+            // the size of the files is known before they are read
+            let file_size = fs::metadata(file)
+                .map(|metadata| metadata.len() as usize)
+                .unwrap_or_default();
+            if is_glob(path) && size + file_size > limit {
+                warn(format!(
+                    "The files of `@{}` are larger than {} KB, {} of them are not attached",
+                    path,
+                    limit / 1000,
+                    files.len() - index
+                ));
+                break;
+            }
+
+            match read(file, lines) {
+                Ok(attachment) => {
+                    size += attachment.content.len();
+                    attachments.push(attachment);
+                }
+                Err(e) => warn(format!("{}, it is sent as text", e)),
+            }
+        }
+    }
+
+    let mut text = prompt.to_string();
+    for attachment in &attachments {
+        text.push_str("\n\n");
+        text.push_str(&attachment.block());
+    }
+
+    Attached {
+        text,
+        size,
+        warnings,
+    }
+}
+
+/// Complete the path of a reference like `@src/ma`
+pub fn complete(reference: &str) -> Option<Completion> {
+    let path = reference.strip_prefix('@')?;
+    let (directory, name) = match path.rsplit_once('/') {
+        Some((directory, name)) => (format!("{}/", directory), name),
+        None => (String::new(), path),
+    };
+
+    let entries = fs::read_dir(match directory.as_str() {
+        "" => PathBuf::from("."),
+        directory => expand_home(directory),
+    })
+    .ok()?;
+
+    let mut candidates: Vec<String> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            // hidden files only when their name is started
+            if !file_name.starts_with(name) || (file_name.starts_with('.') && name.is_empty()) {
+                return None;
+            }
+
+            match entry.path().is_dir() {
+                true => Some(format!("{}/", file_name)),
+                false => Some(file_name),
+            }
+        })
+        .collect();
+    candidates.sort();
+
+    match candidates.len() {
+        0 => None,
+        1 => Some(Completion {
+            text: format!("@{}{}", directory, candidates[0]),
+            candidates: Vec::new(),
+        }),
+        _ => Some(Completion {
+            text: format!("@{}{}", directory, common_prefix(&candidates)),
+            candidates,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: usize = 100_000;
+
+    #[test]
+    fn attach_files() {
+        let attached = attach("explain @Cargo.toml:1 and @Cargo.toml:1.", LIMIT);
+        assert_eq!(
+            attached.text,
+            "explain @Cargo.toml:1 and @Cargo.toml:1.\n\nCargo.toml (lines 1-1):\n```toml\n[package]\n```"
+        );
+        assert_eq!(attached.size, "[package]".len());
+        assert!(attached.warnings.is_empty());
+    }
+
+    #[test]
+    fn references_in_code_are_not_attached() {
+        let prompt = "```\n@Cargo.toml\n```\n`@Cargo.toml` and ``x ` @Cargo.toml``";
+        let attached = attach(prompt, LIMIT);
+        assert_eq!(attached.text, prompt);
+        assert!(attached.warnings.is_empty());
+    }
+
+    #[test]
+    fn unresolved_references_are_sent_as_text() {
+        let prompt = "install @types/node, ask @someone, then @app.route('/') twice @types/node";
+        let attached = attach(prompt, LIMIT);
+        assert_eq!(attached.text, prompt);
+        assert_eq!(attached.size, 0);
+        assert_eq!(
+            attached.warnings,
+            [
+                "No file `types/node`, it is sent as text",
+                "No file `app.route('/`, it is sent as text"
+            ]
+        );
+    }
+
+    #[test]
+    fn files_of_a_glob_up_to_the_limit() {
+        let directory = std::env::temp_dir().join(format!("tenere-glob-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        for name in ["a.txt", "b.txt", "c.txt", "d.txt"] {
+            fs::write(directory.join(name), "x".repeat(1000)).unwrap();
+        }
+
+        let prompt = format!("read @{}/*.txt", directory.display());
+        let attached = attach(&prompt, 2000);
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(attached.size, 2000);
+        assert!(attached.text.contains("a.txt") && attached.text.contains("b.txt"));
+        assert!(!attached.text.contains("c.txt"));
+        assert_eq!(
+            attached.warnings,
+            [format!(
+                "The files of `@{}/*.txt` are larger than 2 KB, 2 of them are not attached",
+                directory.display()
+            )]
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::AppResult,
+    chat::{Chat, Message},
+    history::{data_directory, Archive},
+    llm::LLMRole,
+};
+
+const AUTOSAVE_FILE: &str = "tenere.autosave";
+
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+// Chat of the autosave file. The file name of its archive in the history is not
+// part of the archive, it is saved next to it.
+#[derive(Serialize, Deserialize)]
+struct SavedChat {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    archive: Option<String>,
+
+    #[serde(flatten)]
+    chat: Archive,
+}
+
+/// Copy of the chats of the tabs, kept up to date so it can be written to disk
+/// on exit or from the panic hook.
+#[derive(Debug, Clone)]
+pub struct Autosave {
+    // `None` until it is updated from the chats, the autosave file of the
+    // previous session is kept until then
+    snapshot: Arc<Mutex<Option<Vec<Archive>>>>,
+    dirty: bool,
+    last_save: Instant,
+}
+
+impl Default for Autosave {
+    fn default() -> Self {
+        Self {
+            snapshot: Arc::new(Mutex::new(None)),
+            dirty: false,
+            last_save: Instant::now(),
+        }
+    }
+}
+
+impl Autosave {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn path() -> PathBuf {
+        data_directory().join(AUTOSAVE_FILE)
+    }
+
+    /// Load the chats autosaved by the previous session, older versions saved a single chat
+    pub fn load() -> Vec<Archive> {
+        let Ok(text) = fs::read_to_string(Self::path()) else {
+            return Vec::new();
+        };
+
+        let archives = match serde_json::from_str::<Vec<SavedChat>>(&text) {
+            Ok(saved) => saved
+                .into_iter()
+                .map(|saved| Archive {
+                    file_name: saved.archive.unwrap_or_default(),
+                    ..saved.chat
+                })
+                .collect(),
+            Err(_) => vec![Archive::parse(&text)],
+        };
+
+        archives
+            .into_iter()
+            .filter(|archive| !archive.messages.is_empty())
+            .collect()
+    }
+
+    // the archive of the chat, with the answer being streamed
+    fn archive(chat: &Chat) -> Archive {
+        let mut messages = chat.messages.clone();
+        if !chat.answer.plain_answer.is_empty() {
+            messages.push(Message {
+                role: LLMRole::ASSISTANT,
+                content: chat.answer.plain_answer.clone(),
+                alternatives: chat.answer.alternatives.clone(),
+                metadata: None,
+            });
+        }
+
+        let mut archive = Archive::new(messages);
+        archive.file_name = chat.archive.clone().unwrap_or_default();
+        archive.parent = chat.parent.clone();
+        archive
+    }
+
+    /// Update the snapshot from the chats, the empty ones are left out
+    pub fn update(&mut self, chats: &[&Chat]) {
+        let archives: Vec<Archive> = chats
+            .iter()
+            .map(|chat| Self::archive(chat))
+            .filter(|archive| !archive.messages.is_empty())
+            .collect();
+
+        if let Ok(mut snapshot) = self.snapshot.lock() {
+            let changed = match snapshot.as_ref() {
+                Some(saved) => {
+                    saved.len() != archives.len()
+                        || saved.iter().zip(&archives).any(|(saved, archive)| {
+                            saved.messages != archive.messages
+                                || saved.parent != archive.parent
+                                || saved.file_name != archive.file_name
+                        })
+                }
+                None => true,
+            };
+
+            if changed {
+                *snapshot = Some(archives);
+                self.dirty = true;
+            }
+        }
+    }
+
+    /// Save the snapshot if it changed since the last save and the interval has elapsed
+    pub fn tick(&mut self, chats: &[&Chat]) -> AppResult<()> {
+        self.update(chats);
+
+        if self.dirty && self.last_save.elapsed() >= AUTOSAVE_INTERVAL {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    pub fn save(&mut self) -> AppResult<()> {
+        self.write()?;
+        self.dirty = false;
+        self.last_save = Instant::now();
+        Ok(())
+    }
+
+    /// Write the snapshot, or remove the autosave file if all the chats are empty.
+    /// Nothing is written before the first update. Also called from the panic hook.
+    pub fn write(&self) -> AppResult<()> {
+        // `try_lock` to never block in the panic hook
+        if let Ok(snapshot) = self.snapshot.try_lock() {
+            if let Some(archives) = snapshot.as_ref() {
+                if !archives.is_empty() {
+                    let saved: Vec<SavedChat> = archives
+                        .iter()
+                        .map(|archive| SavedChat {
+                            archive: Some(archive.file_name.clone())
+                                .filter(|name| !name.is_empty()),
+                            chat: archive.clone(),
+                        })
+                        .collect();
+                    fs::write(Self::path(), serde_json::to_string_pretty(&saved)?)?;
+                } else if Self::path().exists() {
+                    fs::remove_file(Self::path())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn clear(&mut self) -> AppResult<()> {
+        if let Ok(mut snapshot) = self.snapshot.lock() {
+            *snapshot = Some(Vec::new());
+        }
+        self.save()
+    }
+}
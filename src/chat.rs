@@ -1,41 +1,431 @@
-use std::{rc::Rc, sync::atomic::AtomicBool};
+use std::{
+    cell::{Cell, Ref, RefCell},
+    rc::Rc,
+    sync::atomic::AtomicBool,
+    time::Instant,
+};
+
+use chrono::{DateTime, Local};
 
 use ratatui::{
-    layout::Rect,
-    text::Text,
+    layout::{Constraint, Layout, Rect},
+    style::Stylize,
+    text::{Line, Span, Text},
     widgets::{Block, Paragraph, Wrap},
     Frame,
 };
 
-use crate::{formatter::Formatter, llm::LLMAnswer};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    comparison::Comparison,
+    formatter::Formatter,
+    keymap::Keymap,
+    llm::{LLMAnswer, LLMRole, Stats},
+    search::Search,
+    theme::Theme,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    pub role: LLMRole,
+    pub content: String,
+
+    // other responses generated for the same prompt
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alternatives: Vec<Alternative>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Metadata>,
+}
+
+impl Message {
+    pub fn new(role: LLMRole, content: String) -> Self {
+        Self {
+            role,
+            content,
+            alternatives: Vec::new(),
+            metadata: None,
+        }
+    }
+}
+
+/// Another response generated for the same prompt, with how it was generated
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "SavedAlternative")]
+pub struct Alternative {
+    pub content: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Metadata>,
+}
+
+// older versions saved the content of the alternatives only
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SavedAlternative {
+    Content(String),
+    Alternative {
+        content: String,
+        #[serde(default)]
+        metadata: Option<Metadata>,
+    },
+}
+
+impl From<SavedAlternative> for Alternative {
+    fn from(saved: SavedAlternative) -> Self {
+        match saved {
+            SavedAlternative::Content(content) => Self {
+                content,
+                metadata: None,
+            },
+            SavedAlternative::Alternative { content, metadata } => Self { content, metadata },
+        }
+    }
+}
+
+/// When a message was sent or received and, for the answers, how it was generated
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    pub timestamp: DateTime<Local>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+
+    // seconds between the request and the first token of the answer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_to_first_token: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_per_second: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_tokens: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completion_tokens: Option<u64>,
+}
+
+impl Metadata {
+    pub fn now() -> Self {
+        Self {
+            timestamp: Local::now(),
+            model: None,
+            time_to_first_token: None,
+            tokens_per_second: None,
+            prompt_tokens: None,
+            completion_tokens: None,
+        }
+    }
+
+    // dim line displayed under the message
+    fn footer(&self, theme: &Theme) -> Line<'static> {
+        let timestamp = if self.timestamp.date_naive() == Local::now().date_naive() {
+            self.timestamp.format("%H:%M:%S")
+        } else {
+            self.timestamp.format("%Y-%m-%d %H:%M:%S")
+        };
+
+        let mut parts = vec![timestamp.to_string()];
+
+        if let Some(model) = &self.model {
+            parts.push(model.clone());
+        }
+        if let Some(ttft) = self.time_to_first_token {
+            parts.push(format!("first token {:.2}s", ttft));
+        }
+        if let Some(speed) = self.tokens_per_second {
+            parts.push(format!("{:.1} tok/s", speed));
+        }
+        match (self.prompt_tokens, self.completion_tokens) {
+            (Some(prompt), Some(completion)) => parts.push(format!(
+                "{} prompt + {} completion tokens",
+                prompt, completion
+            )),
+            (None, Some(completion)) => parts.push(format!("{} tokens", completion)),
+            _ => {}
+        }
+
+        Line::from(parts.join(" · ")).fg(theme.dim)
+    }
+}
+
+/// Content of the fenced code blocks of a markdown text.
+/// A block that is not closed, e.g. while it is streamed, goes to the end of the text.
+pub fn code_blocks(text: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    // fence opening the current block and the lines of the block
+    let mut block: Option<(String, Vec<&str>)> = None;
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let fence: String = trimmed
+            .chars()
+            .take_while(|c| *c == '`' || *c == '~')
+            .collect();
+
+        match block.as_mut() {
+            None => {
+                if fence.len() >= 3 && fence.chars().all(|c| c == fence.chars().next().unwrap()) {
+                    block = Some((fence, Vec::new()));
+                }
+            }
+            Some((opening, lines)) => {
+                if fence.starts_with(opening.as_str()) && trimmed[fence.len()..].trim().is_empty() {
+                    blocks.push(lines.join("\n"));
+                    block = None;
+                } else {
+                    lines.push(line);
+                }
+            }
+        }
+    }
+
+    if let Some((_, lines)) = block {
+        blocks.push(lines.join("\n"));
+    }
+
+    blocks
+}
+
+// Number of rows of each line once wrapped, for the width of the last render.
+// The lines that are changed or removed are forgotten with `invalidate`.
+#[derive(Debug, Clone, Default)]
+struct Rows {
+    width: Cell<u16>,
+    counts: RefCell<Vec<usize>>,
+}
+
+impl Rows {
+    fn invalidate(&self, line: usize) {
+        self.counts.borrow_mut().truncate(line);
+    }
+
+    // the lines not counted yet are wrapped with the same rules as the rendered paragraph
+    fn counts(&self, lines: &[Line], width: u16) -> Ref<'_, Vec<usize>> {
+        {
+            let mut counts = self.counts.borrow_mut();
+            if self.width.replace(width) != width {
+                counts.clear();
+            }
+            counts.truncate(lines.len());
+
+            let counted = counts.len();
+            counts.extend(lines[counted..].iter().map(|line| {
+                Paragraph::new(line.clone())
+                    .wrap(Wrap { trim: false })
+                    .line_count(width)
+            }));
+        }
+        self.counts.borrow()
+    }
+}
 
 #[derive(Debug, Clone, Default)]
 pub struct Answer<'a> {
     pub plain_answer: String,
     pub formatted_answer: Text<'a>,
+    // previous responses when the answer is regenerated
+    pub alternatives: Vec<Alternative>,
+    // length of the beginning of `plain_answer` made of finished blocks,
+    // and the number of lines they take in `formatted_answer`
+    finished_len: usize,
+    finished_lines: usize,
+    // when the answer was requested, when its first token was received and when it ended
+    pub requested: Option<Instant>,
+    first_token: Option<Instant>,
+    finished: Option<Instant>,
+    stats: Option<Stats>,
+    // metadata of an answer that was not requested, e.g. restored from the history
+    pub metadata: Option<Metadata>,
+    rows: Rows,
+}
+
+impl Answer<'_> {
+    // answer requested now, to measure how it is generated
+    pub fn requested() -> Self {
+        Self {
+            requested: Some(Instant::now()),
+            ..Default::default()
+        }
+    }
+
+    // add a chunk of the stream to the answer
+    pub fn push(&mut self, chunk: &str, formatter: &Formatter) {
+        if self.first_token.is_none() {
+            self.first_token = Some(Instant::now());
+        }
+        self.plain_answer.push_str(chunk);
+        self.format(formatter);
+    }
+
+    pub fn set_stats(&mut self, stats: Stats) {
+        self.stats = Some(stats);
+    }
+
+    // end of the stream, the metadata can be taken later
+    pub fn finish(&mut self) {
+        if self.finished.is_none() {
+            self.finished = Some(Instant::now());
+        }
+    }
+
+    // number of rows of the formatted answer once wrapped
+    pub fn height(&self, width: u16) -> usize {
+        self.rows
+            .counts(&self.formatted_answer.lines, width)
+            .iter()
+            .sum()
+    }
+
+    /// How the answer was generated, taken once it is finished
+    pub fn metadata(&mut self) -> Option<Metadata> {
+        let Some(requested) = self.requested else {
+            return self.metadata.take();
+        };
+
+        let stats = self.stats.take().unwrap_or_default();
+
+        // the generation time reported by the backend, or the time of the stream
+        let finished = self.finished.unwrap_or_else(Instant::now);
+        let generation_time = stats
+            .generation_time
+            .or(self.first_token.map(|first_token| finished - first_token));
+
+        Some(Metadata {
+            model: stats.model,
+            time_to_first_token: self
+                .first_token
+                .map(|first_token| (first_token - requested).as_secs_f64()),
+            tokens_per_second: stats
+                .completion_tokens
+                .zip(generation_time)
+                .filter(|(_, time)| !time.is_zero())
+                .map(|(tokens, time)| tokens as f64 / time.as_secs_f64()),
+            prompt_tokens: stats.prompt_tokens,
+            completion_tokens: stats.completion_tokens,
+            ..Metadata::now()
+        })
+    }
+
+    // Only the unfinished block at the end of the answer is formatted again,
+    // the lines of the finished blocks are kept
+    fn format(&mut self, formatter: &Formatter) {
+        let chunk = |start: usize, end: usize| {
+            let text = formatter.format(&self.plain_answer[start..end]);
+            match start {
+                0 => with_prefix("🤖: ", text),
+                _ => text,
+            }
+        };
+
+        self.formatted_answer.lines.truncate(self.finished_lines);
+        self.rows.invalidate(self.finished_lines);
+
+        let finished_len =
+            self.finished_len + finished_blocks_len(&self.plain_answer[self.finished_len..]);
+
+        if finished_len > self.finished_len {
+            self.formatted_answer
+                .extend(chunk(self.finished_len, finished_len));
+            self.finished_len = finished_len;
+            self.finished_lines = self.formatted_answer.lines.len();
+        }
+
+        self.formatted_answer
+            .extend(chunk(self.finished_len, self.plain_answer.len()));
+    }
+}
+
+// The role is added to the first line of the formatted message, the message alone is markdown
+fn with_prefix(prefix: &'static str, mut text: Text<'static>) -> Text<'static> {
+    match text.lines.first_mut() {
+        Some(line) => line.spans.insert(0, Span::raw(prefix)),
+        None => text.lines.push(Line::raw(prefix)),
+    }
+    text
+}
+
+// Length of the finished markdown blocks at the beginning of the text.
+// A block is finished by a blank line or by the end of a code block.
+fn finished_blocks_len(text: &str) -> usize {
+    let mut len = 0;
+    let mut position = 0;
+    let mut fence: Option<String> = None;
+
+    // only complete lines
+    for line in text
+        .split_inclusive('\n')
+        .filter(|line| line.ends_with('\n'))
+    {
+        position += line.len();
+
+        let trimmed = line.trim();
+        let line_fence: String = trimmed
+            .chars()
+            .take_while(|c| *c == '`' || *c == '~')
+            .collect();
+
+        match fence.as_ref() {
+            None if line_fence.len() >= 3 => fence = Some(line_fence),
+            None if trimmed.is_empty() => len = position,
+            Some(opening) if line_fence.starts_with(opening.as_str()) && trimmed == line_fence => {
+                fence = None;
+                len = position;
+            }
+            _ => {}
+        }
+    }
+
+    len
 }
 
 #[derive(Debug, Clone)]
 pub struct Chat<'a> {
+    pub messages: Vec<Message>,
     pub plain_chat: Vec<String>,
     pub formatted_chat: Text<'a>,
+    // index of the first line of each message in `formatted_chat`
+    offsets: Vec<usize>,
+    rows: Rows,
     pub answer: Answer<'a>,
+    pub streaming: bool,
+    pub selected: Option<usize>,
     pub scroll: u16,
     area_height: u16,
     area_width: u16,
     pub automatic_scroll: Rc<AtomicBool>,
+    // name of the archive file the chat was resumed from
+    pub archive: Option<String>,
+    // name of the archive file the chat was forked from
+    pub parent: Option<String>,
+    pub search: Option<Search>,
+    // lines selected by dragging the mouse: the line where the drag started and the current one
+    drag: Option<(usize, Option<usize>)>,
+    // answers of several models to the last message, one of them is kept
+    pub comparison: Option<Comparison<'a>>,
 }
 
 impl Default for Chat<'_> {
     fn default() -> Self {
         Self {
+            messages: Vec::new(),
             plain_chat: Vec::new(),
             formatted_chat: Text::raw(""),
+            offsets: Vec::new(),
+            rows: Rows::default(),
             answer: Answer::default(),
+            streaming: false,
+            selected: None,
             scroll: 0,
             area_height: 0,
             area_width: 0,
             automatic_scroll: Rc::new(AtomicBool::new(true)),
+            archive: None,
+            parent: None,
+            search: None,
+            drag: None,
+            comparison: None,
         }
     }
 }
@@ -45,78 +435,710 @@ impl Chat<'_> {
         Self::default()
     }
 
+    /// Rebuild a chat from saved messages, e.g. when resuming a chat from the history
+    pub fn from_messages(messages: Vec<Message>, formatter: &Formatter) -> Self {
+        let mut chat = Self::default();
+
+        for message in messages {
+            chat.push_saved_message(message, formatter);
+        }
+
+        chat
+    }
+
+    // add a message that was already sent or received
+    fn push_saved_message(&mut self, message: Message, formatter: &Formatter) {
+        match message.role {
+            LLMRole::USER => self.push_message(message, formatter),
+            LLMRole::ASSISTANT => {
+                self.answer.alternatives = message.alternatives;
+                self.answer.metadata = message.metadata;
+                self.answer.plain_answer = message.content;
+                self.answer.formatted_answer =
+                    with_prefix("🤖: ", formatter.format(&self.answer.plain_answer));
+                self.handle_answer(LLMAnswer::EndAnswer, formatter);
+            }
+            LLMRole::SYSTEM => self.push_system_message(message.content),
+        }
+    }
+
+    pub fn push_user_message(&mut self, content: String, formatter: &Formatter) {
+        let mut message = Message::new(LLMRole::USER, content);
+        message.metadata = Some(Metadata::now());
+        self.push_message(message, formatter);
+    }
+
+    /// System messages are sent to the backend but not displayed
+    pub fn push_system_message(&mut self, content: String) {
+        self.offsets.push(self.formatted_chat.lines.len());
+        self.plain_chat.push(String::new());
+        self.messages.push(Message::new(LLMRole::SYSTEM, content));
+    }
+
+    fn push_message(&mut self, message: Message, formatter: &Formatter) {
+        let content = &message.content;
+        self.plain_chat.push(format!("👤 : {}\n", content));
+
+        if self.formatted_chat.width() == 0 {
+            self.offsets.push(0);
+            self.formatted_chat = with_prefix("👤: ", formatter.format(&format!("{}\n", content)));
+            self.rows.invalidate(0);
+        } else {
+            self.offsets.push(self.formatted_chat.lines.len());
+            self.formatted_chat.extend(with_prefix(
+                "👤: ",
+                formatter.format(&format!("{}\n", content)),
+            ));
+        }
+
+        if let Some(metadata) = &message.metadata {
+            self.formatted_chat
+                .lines
+                .push(metadata.footer(formatter.theme));
+        }
+
+        self.messages.push(message);
+    }
+
     pub fn handle_answer(&mut self, event: LLMAnswer, formatter: &Formatter) {
         match event {
             LLMAnswer::StartAnswer => {
                 self.formatted_chat.lines.pop();
+                self.rows.invalidate(self.formatted_chat.lines.len());
             }
 
             LLMAnswer::Answer(answer) => {
-                self.answer.plain_answer.push_str(answer.as_str());
+                self.answer.push(&answer, formatter);
+            }
 
-                self.answer.formatted_answer =
-                    formatter.format(format!("🤖: {}", &self.answer.plain_answer).as_str());
+            LLMAnswer::Stats(stats) => {
+                self.answer.set_stats(stats);
             }
 
             LLMAnswer::EndAnswer => {
+                self.offsets.push(self.formatted_chat.lines.len());
+
                 self.formatted_chat
                     .extend(self.answer.formatted_answer.clone());
 
+                if !self.answer.alternatives.is_empty() {
+                    self.formatted_chat.lines.push(
+                        Line::from(format!(
+                            "({} responses, < or > to switch)",
+                            self.answer.alternatives.len() + 1
+                        ))
+                        .fg(formatter.theme.dim),
+                    );
+                }
+
+                let metadata = self.answer.metadata();
+                if let Some(metadata) = &metadata {
+                    self.formatted_chat
+                        .lines
+                        .push(metadata.footer(formatter.theme));
+                }
+
                 self.formatted_chat.extend(Text::raw("\n"));
 
                 self.plain_chat
-                    .push(format!("🤖: {}", self.answer.plain_answer));
+                    .push(format!("🤖: {}\n", self.answer.plain_answer));
+
+                self.messages.push(Message {
+                    role: LLMRole::ASSISTANT,
+                    content: self.answer.plain_answer.clone(),
+                    alternatives: std::mem::take(&mut self.answer.alternatives),
+                    metadata,
+                });
+
+                self.answer = Answer::default();
+                self.streaming = false;
+            }
+
+            LLMAnswer::Error(error) => {
+                self.formatted_chat
+                    .lines
+                    .push(Line::from(format!("🤖: {}", error)).fg(formatter.theme.error));
+                self.formatted_chat.extend(Text::raw("\n"));
 
                 self.answer = Answer::default();
+                self.streaming = false;
             }
         }
     }
 
-    pub fn height(&self) -> usize {
-        let mut chat = self.formatted_chat.clone();
+    // label of the tab: the beginning of the first message
+    pub fn title(&self) -> String {
+        match self.messages.first() {
+            Some(message) => {
+                let line = message.content.lines().next().unwrap_or_default();
+                let title: String = line.chars().take(20).collect();
+                if title.len() < message.content.len() {
+                    format!("{}…", title)
+                } else {
+                    title
+                }
+            }
+            None => "New chat".to_string(),
+        }
+    }
 
-        chat.extend(self.answer.formatted_answer.clone());
+    /// Keep the selected answer of the comparison in the chat.
+    /// Returns the content of the answer.
+    pub fn keep_comparison(&mut self, formatter: &Formatter) -> Option<String> {
+        let comparison = self.comparison.take()?;
 
-        let nb_lines = chat.lines.len() + 3;
-        chat.lines.iter().fold(nb_lines, |acc, line| {
-            acc + line.width() / self.area_width as usize
-        })
+        self.answer = comparison.pick();
+        let content = self.answer.plain_answer.clone();
+        self.handle_answer(LLMAnswer::EndAnswer, formatter);
+
+        Some(content)
+    }
+
+    /// Remove the message at `index` and all the messages after it
+    pub fn truncate(&mut self, index: usize) {
+        if let Some(offset) = self.offsets.get(index) {
+            self.formatted_chat.lines.truncate(*offset);
+        }
+        if self.formatted_chat.lines.is_empty() {
+            self.formatted_chat = Text::raw("");
+            self.rows.invalidate(0);
+        } else {
+            self.rows.invalidate(self.formatted_chat.lines.len());
+        }
+
+        self.messages.truncate(index);
+        self.plain_chat.truncate(index);
+        self.offsets.truncate(index);
+        self.selected = None;
+    }
+
+    /// Remove the last answer to generate a new one. The removed answer is kept as an alternative.
+    pub fn regenerate(&mut self) {
+        if let Some(message) = self.messages.last().cloned() {
+            self.truncate(self.messages.len() - 1);
+
+            self.answer.alternatives = message.alternatives;
+            self.answer.alternatives.push(Alternative {
+                content: message.content,
+                metadata: message.metadata,
+            });
+        }
+    }
+
+    /// Replace the selected answer, or the last one, with its next (or previous) alternative.
+    /// The messages after it are kept. Returns the index of the answer.
+    pub fn cycle_alternatives(&mut self, forward: bool, formatter: &Formatter) -> Option<usize> {
+        let index = match self.selected {
+            Some(index) => index,
+            None => self.messages.len().checked_sub(1)?,
+        };
+
+        let mut message = self.messages.get(index).cloned()?;
+        if message.role != LLMRole::ASSISTANT || message.alternatives.is_empty() {
+            return None;
+        }
+
+        // the alternatives followed by the answer form a ring in the generation order,
+        // each response with the metadata of its generation
+        let current = Alternative {
+            content: message.content,
+            metadata: message.metadata,
+        };
+        let next = if forward {
+            message.alternatives.push(current);
+            message.alternatives.remove(0)
+        } else {
+            message.alternatives.insert(0, current);
+            message.alternatives.pop().unwrap()
+        };
+        message.content = next.content;
+        message.metadata = next.metadata;
+
+        // the answer and the messages after it are formatted again
+        let following = self.messages[index + 1..].to_vec();
+        let selected = self.selected;
+
+        self.truncate(index);
+        self.push_saved_message(message, formatter);
+        for message in following {
+            self.push_saved_message(message, formatter);
+        }
+        self.selected = selected;
+
+        Some(index)
+    }
+
+    /// Replace the last line of the chat, e.g. the spinner waiting for the answer
+    pub fn replace_last_line(&mut self, line: Line<'static>) {
+        self.formatted_chat.lines.pop();
+        self.rows.invalidate(self.formatted_chat.lines.len());
+        self.formatted_chat.lines.push(line);
+    }
+
+    pub fn selected_message(&self) -> Option<&Message> {
+        self.selected.and_then(|index| self.messages.get(index))
+    }
+
+    // the content of the selected message, or of the last answer when no message is selected,
+    // the answer being streamed included
+    pub fn target_content(&self) -> Option<&str> {
+        if let Some(message) = self.selected_message() {
+            return Some(&message.content);
+        }
+        if !self.answer.plain_answer.is_empty() {
+            return Some(&self.answer.plain_answer);
+        }
+        self.messages
+            .iter()
+            .rev()
+            .find(|message| message.role == LLMRole::ASSISTANT)
+            .map(|message| message.content.as_str())
+    }
+
+    pub fn select_previous(&mut self) {
+        let start = self.selected.unwrap_or(self.messages.len());
+        if let Some(index) = (0..start)
+            .rev()
+            .find(|i| self.messages[*i].role != LLMRole::SYSTEM)
+        {
+            self.selected = Some(index);
+            self.scroll_to_selected();
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        let Some(start) = self.selected else {
+            return;
+        };
+        if let Some(index) =
+            (start + 1..self.messages.len()).find(|i| self.messages[*i].role != LLMRole::SYSTEM)
+        {
+            self.selected = Some(index);
+            self.scroll_to_selected();
+        }
+    }
+
+    fn scroll_to_selected(&mut self) {
+        if let Some(offset) = self.selected.map(|index| self.offsets[index]) {
+            self.automatic_scroll
+                .store(false, std::sync::atomic::Ordering::Relaxed);
+            self.scroll = self.line_row(offset).try_into().unwrap_or(u16::MAX);
+        }
+    }
+
+    // rows of each line of the chat once wrapped, the lines of the answer being streamed included
+    fn line_rows(&self) -> Vec<usize> {
+        let chat = self
+            .rows
+            .counts(&self.formatted_chat.lines, self.area_width);
+        let answer = self
+            .answer
+            .rows
+            .counts(&self.answer.formatted_answer.lines, self.area_width);
+
+        chat.iter().chain(answer.iter()).copied().collect()
+    }
+
+    // number of rows above the line
+    fn line_row(&self, line: usize) -> usize {
+        self.line_rows().iter().take(line).sum()
+    }
+
+    // first line at the row or below it
+    fn line_at_row(&self, row: usize) -> usize {
+        let mut rows = 0;
+        for (index, count) in self.line_rows().into_iter().enumerate() {
+            if rows >= row {
+                return index;
+            }
+            rows += count;
+        }
+        usize::MAX
+    }
+
+    // line displayed on the row of the chat area
+    fn line_on_row(&self, row: u16) -> Option<usize> {
+        let row =
+            usize::from(self.scroll) + usize::from(row.min(self.area_height.saturating_sub(1)));
+        let mut rows = 0;
+        for (index, count) in self.line_rows().into_iter().enumerate() {
+            rows += count;
+            if rows > row {
+                return Some(index);
+            }
+        }
+        None
+    }
+
+    /// Start selecting lines from the row of the chat area
+    pub fn start_drag(&mut self, row: u16) {
+        self.drag = self.line_on_row(row).map(|line| (line, None));
+    }
+
+    pub fn drag(&mut self, row: u16) {
+        let line = self.line_on_row(row);
+        if let Some((_, end)) = self.drag.as_mut() {
+            *end = line.or(*end);
+        }
+    }
+
+    /// Stop the selection, the text of the lines selected if the mouse was dragged
+    pub fn end_drag(&mut self) -> Option<(String, usize)> {
+        let (start, end) = match self.drag.take()? {
+            (start, Some(end)) => (start.min(end), start.max(end)),
+            (_, None) => return None,
+        };
+
+        let lines: Vec<String> = self
+            .formatted_chat
+            .lines
+            .iter()
+            .chain(&self.answer.formatted_answer.lines)
+            .skip(start)
+            .take(end - start + 1)
+            .map(|line| line.to_string())
+            .collect();
+        Some((lines.join("\n"), lines.len()))
+    }
+
+    pub fn start_search(&mut self) {
+        self.search = Some(Search::new(self.line_at_row(self.scroll.into())));
+    }
+
+    /// Find the matches of the query again and go to the first one from where the search started
+    pub fn update_search(&mut self) {
+        if let Some(search) = self.search.as_mut() {
+            search.find(
+                self.formatted_chat
+                    .lines
+                    .iter()
+                    .chain(&self.answer.formatted_answer.lines),
+            );
+            search.reset();
+        }
+        self.scroll_to_match();
+    }
+
+    pub fn next_match(&mut self, forward: bool) {
+        if let Some(search) = self.search.as_mut() {
+            if forward {
+                search.next();
+            } else {
+                search.previous();
+            }
+        }
+        self.scroll_to_match();
+    }
+
+    // scroll to have the current match in the middle of the view
+    fn scroll_to_match(&mut self) {
+        if let Some(line) = self.search.as_ref().and_then(Search::line) {
+            self.automatic_scroll
+                .store(false, std::sync::atomic::Ordering::Relaxed);
+            self.scroll = self
+                .line_row(line)
+                .saturating_sub((self.area_height / 2).into())
+                .try_into()
+                .unwrap_or(u16::MAX);
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        let chat: usize = self
+            .rows
+            .counts(&self.formatted_chat.lines, self.area_width)
+            .iter()
+            .sum();
+        chat + self.answer.height(self.area_width)
+    }
+
+    // scroll showing the end of the chat
+    fn max_scroll(&self) -> u16 {
+        self.height()
+            .saturating_sub(self.area_height.into())
+            .try_into()
+            .unwrap_or(u16::MAX)
     }
 
     pub fn move_to_bottom(&mut self) {
-        self.scroll = (self.formatted_chat.height() + self.answer.formatted_answer.height())
-            .saturating_sub((self.area_height - 2).into()) as u16;
+        self.scroll = self.max_scroll();
     }
 
     pub fn move_to_top(&mut self) {
         self.scroll = 0;
     }
 
-    pub fn render(&mut self, frame: &mut Frame, area: Rect) {
+    pub fn render(&mut self, frame: &mut Frame, area: Rect, theme: &Theme, keymap: &Keymap) {
+        if let Some(comparison) = self.comparison.as_mut() {
+            comparison.render(frame, area, theme, keymap);
+            return;
+        }
+
         let mut text = self.formatted_chat.clone();
         text.extend(self.answer.formatted_answer.clone());
 
+        // highlight the selected message
+        if let Some(index) = self.selected {
+            let start = self.offsets[index];
+            let end = self
+                .offsets
+                .get(index + 1)
+                .copied()
+                .unwrap_or(self.formatted_chat.lines.len());
+
+            text.lines[start..end]
+                .iter_mut()
+                .for_each(|line| line.style = line.style.bg(theme.selection));
+        }
+
+        if let Some((start, Some(end))) = self.drag {
+            text.lines
+                .iter_mut()
+                .skip(start.min(end))
+                .take(start.abs_diff(end) + 1)
+                .for_each(|line| line.style = line.style.bg(theme.selection));
+        }
+
+        // the matches are found again as the answer is streamed
+        let area = if let Some(search) = self.search.as_mut() {
+            search.find(
+                self.formatted_chat
+                    .lines
+                    .iter()
+                    .chain(&self.answer.formatted_answer.lines),
+            );
+            search.highlight(&mut text.lines, theme);
+
+            let [area, status_area] =
+                Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(area);
+            frame.render_widget(Paragraph::new(search.status(theme)), status_area);
+            area
+        } else {
+            area
+        };
+
         self.area_height = area.height;
         self.area_width = area.width;
 
-        let scroll: u16 = {
-            if self
-                .automatic_scroll
-                .load(std::sync::atomic::Ordering::Relaxed)
-            {
-                let scroll = self.height().saturating_sub(self.area_height.into()) as u16;
-                self.scroll = scroll;
-                scroll
-            } else {
-                self.scroll
-            }
+        // do not scroll past the end of the chat
+        self.scroll = if self
+            .automatic_scroll
+            .load(std::sync::atomic::Ordering::Relaxed)
+        {
+            self.max_scroll()
+        } else {
+            self.scroll.min(self.max_scroll())
         };
 
         let chat = Paragraph::new(text)
-            .scroll((scroll, 0))
+            .scroll((self.scroll, 0))
             .wrap(Wrap { trim: false })
             .block(Block::default());
 
         frame.render_widget(chat, area);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ratatui::{backend::TestBackend, Terminal};
+
+    use super::*;
+    use crate::config::MarkdownRenderer;
+
+    fn chat(lines: &[&str]) -> Chat<'static> {
+        let mut chat = Chat::new();
+        chat.formatted_chat = Text::from(
+            lines
+                .iter()
+                .map(|line| Line::raw(line.to_string()))
+                .collect::<Vec<_>>(),
+        );
+        chat
+    }
+
+    fn draw(chat: &mut Chat, width: u16, height: u16) -> Terminal<TestBackend> {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal
+            .draw(|frame| chat.render(frame, frame.area(), &Theme::default(), &Keymap::default()))
+            .unwrap();
+        terminal
+    }
+
+    #[test]
+    fn code_blocks_of_a_message() {
+        let text = "a\n```rust\nfn main() {}\n```\nb\n~~~\n```\nnested\n```\n~~~\n";
+        assert_eq!(code_blocks(text), ["fn main() {}", "```\nnested\n```"]);
+    }
+
+    #[test]
+    fn code_block_not_closed() {
+        let text = "a\n```\nfirst\n```\n````python\nprint(1)\n```\nprint(2)";
+        assert_eq!(code_blocks(text), ["first", "print(1)\n```\nprint(2)"]);
+    }
+
+    #[test]
+    fn height_of_wide_text() {
+        let mut chat = chat(&["你好世界你好世界", "hello world again"]);
+        let terminal = draw(&mut chat, 10, 4);
+
+        assert_eq!(chat.height(), 5);
+        assert_eq!(chat.scroll, 1);
+        terminal.backend().assert_buffer_lines([
+            "好世界    ",
+            "hello     ",
+            "world     ",
+            "again     ",
+        ]);
+    }
+
+    #[test]
+    fn areas_too_small() {
+        let mut chat = chat(&["你好世界", "hello world"]);
+        for (width, height) in [(0, 0), (0, 1), (1, 0), (1, 1), (2, 1)] {
+            draw(&mut chat, width, height);
+            assert!(usize::from(chat.scroll) <= chat.height());
+        }
+
+        // a wide character does not fit in one column
+        let terminal = draw(&mut chat, 1, 1);
+        assert_eq!(chat.height(), 11);
+        terminal.backend().assert_buffer_lines(["d"]);
+    }
+
+    #[test]
+    fn move_to_bottom_after_resize() {
+        let mut chat = chat(&["a long line of the chat", "the last line"]);
+        chat.automatic_scroll
+            .store(false, std::sync::atomic::Ordering::Relaxed);
+
+        draw(&mut chat, 40, 2);
+        chat.move_to_bottom();
+        assert_eq!(chat.scroll, 0);
+
+        // the rows are counted again for the new width
+        draw(&mut chat, 10, 2);
+        chat.move_to_bottom();
+        let terminal = draw(&mut chat, 10, 2);
+        assert_eq!(chat.height(), 5);
+        assert_eq!(chat.scroll, 3);
+        terminal
+            .backend()
+            .assert_buffer_lines(["the last  ", "line      "]);
+    }
+
+    #[test]
+    fn height_after_a_line_is_replaced() {
+        let mut chat = chat(&["hello", "🤖: Waiting"]);
+        draw(&mut chat, 20, 2);
+        assert_eq!(chat.height(), 2);
+
+        chat.replace_last_line(Line::raw("🤖: Waiting for the answer"));
+        assert_eq!(chat.height(), 3);
+    }
+
+    #[test]
+    fn drag_to_select_lines() {
+        let mut chat = chat(&["first", "a long second line", "third", "fourth"]);
+        chat.automatic_scroll
+            .store(false, std::sync::atomic::Ordering::Relaxed);
+        draw(&mut chat, 10, 3);
+
+        // a click without a drag selects nothing
+        chat.start_drag(0);
+        assert_eq!(chat.end_drag(), None);
+
+        // the rows of the wrapped line belong to it, the rows past the area to the last one shown
+        chat.start_drag(2);
+        chat.drag(0);
+        assert_eq!(
+            chat.end_drag(),
+            Some(("first\na long second line".to_string(), 2))
+        );
+
+        chat.scroll = 3;
+        chat.start_drag(0);
+        chat.drag(10);
+        assert_eq!(
+            chat.end_drag(),
+            Some(("a long second line\nthird\nfourth".to_string(), 3))
+        );
+    }
+
+    #[test]
+    fn cycle_alternatives_with_their_metadata() {
+        let theme = Theme::default();
+        let (config, assets) = Formatter::init(&theme);
+        let formatter = Formatter::new(&config, &assets, MarkdownRenderer::Native, &theme);
+
+        let model_a = Metadata {
+            model: Some("a".to_string()),
+            ..Metadata::now()
+        };
+        let model_b = Metadata {
+            model: Some("b".to_string()),
+            ..Metadata::now()
+        };
+
+        let mut chat = Chat::new();
+        chat.push_saved_message(Message::new(LLMRole::USER, "hello".to_string()), &formatter);
+        chat.push_saved_message(
+            Message {
+                alternatives: vec![Alternative {
+                    content: "first".to_string(),
+                    metadata: Some(model_a.clone()),
+                }],
+                metadata: Some(model_b.clone()),
+                ..Message::new(LLMRole::ASSISTANT, "second".to_string())
+            },
+            &formatter,
+        );
+
+        assert_eq!(chat.cycle_alternatives(true, &formatter), Some(1));
+        let answer = &chat.messages[1];
+        assert_eq!(answer.content, "first");
+        assert_eq!(answer.metadata, Some(model_a));
+        assert_eq!(answer.alternatives[0].content, "second");
+        assert_eq!(answer.alternatives[0].metadata, Some(model_b));
+    }
+
+    #[test]
+    fn load_alternatives_saved_as_text() {
+        let message: Message = serde_json::from_str(
+            r#"{"role": "assistant", "content": "second", "alternatives": ["first"]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            message.alternatives,
+            [Alternative {
+                content: "first".to_string(),
+                metadata: None
+            }]
+        );
+    }
+
+    #[test]
+    fn role_prefix_outside_of_the_markdown() {
+        let theme = Theme::default();
+        let (config, assets) = Formatter::init(&theme);
+        let formatter = Formatter::new(&config, &assets, MarkdownRenderer::Native, &theme);
+
+        let mut chat = Chat::new();
+        chat.push_saved_message(
+            Message::new(LLMRole::USER, "```\nlet a = 1;\n```".to_string()),
+            &formatter,
+        );
+        chat.push_saved_message(
+            Message::new(LLMRole::ASSISTANT, "- one\n- two".to_string()),
+            &formatter,
+        );
+
+        let lines: Vec<String> = chat
+            .formatted_chat
+            .lines
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+        assert_eq!(lines, ["👤: let a = 1;", "🤖: • one", "• two", ""]);
+    }
+}
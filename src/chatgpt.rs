@@ -1,15 +1,14 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::time::{sleep, Duration};
 
-use crate::event::Event;
 use async_trait::async_trait;
 use regex::Regex;
 use tokio::sync::mpsc::UnboundedSender;
 
 use crate::config::ChatGPTConfig;
-use crate::llm::{LLMAnswer, LLMRole, LLM};
+use crate::llm::{LLMAnswer, LLMRole, Stats, LLM};
 use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
 use serde_json::{json, Value};
 use std;
 use std::collections::HashMap;
@@ -21,6 +20,7 @@ pub struct ChatGPT {
     model: String,
     url: String,
     messages: Vec<HashMap<String, String>>,
+    temperature: Option<f64>,
 }
 
 impl ChatGPT {
@@ -46,6 +46,7 @@ You need to define one whether in the configuration file or as an environment va
             model: config.model,
             url: config.url,
             messages: Vec::new(),
+            temperature: None,
         }
     }
 }
@@ -56,6 +57,14 @@ impl LLM for ChatGPT {
         self.messages = Vec::new();
     }
 
+    fn set_temperature(&mut self, temperature: f64) {
+        self.temperature = Some(temperature);
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.messages.truncate(len);
+    }
+
     fn append_chat_msg(&mut self, msg: String, role: LLMRole) {
         let mut conv: HashMap<String, String> = HashMap::new();
         conv.insert("role".to_string(), role.to_string());
@@ -65,7 +74,7 @@ impl LLM for ChatGPT {
 
     async fn ask(
         &self,
-        sender: UnboundedSender<Event>,
+        sender: UnboundedSender<LLMAnswer>,
         terminate_response_signal: Arc<AtomicBool>,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let mut headers = HeaderMap::new();
@@ -87,23 +96,42 @@ impl LLM for ChatGPT {
 
         messages.extend(self.messages.clone());
 
-        let body: Value = json!({
+        let mut body: Value = json!({
             "model": self.model,
             "messages": messages,
             "stream": true,
+            "stream_options": {"include_usage": true},
         });
 
-        let response = self
+        if let Some(temperature) = self.temperature {
+            body["temperature"] = json!(temperature);
+        }
+
+        let mut response = self
             .client
             .post(&self.url)
-            .headers(headers)
+            .headers(headers.clone())
             .json(&body)
             .send()
             .await?;
 
+        // some compatible servers reject the usage option, ask again without it
+        if response.status() == StatusCode::BAD_REQUEST {
+            if let Some(body) = body.as_object_mut() {
+                body.remove("stream_options");
+            }
+            response = self
+                .client
+                .post(&self.url)
+                .headers(headers)
+                .json(&body)
+                .send()
+                .await?;
+        }
+
         match response.error_for_status() {
             Ok(mut res) => {
-                sender.send(Event::LLMEvent(LLMAnswer::StartAnswer))?;
+                sender.send(LLMAnswer::StartAnswer)?;
                 let re = Regex::new(r"data:\s(.*)")?;
 
                 while let Some(chunk) = res.chunk().await? {
@@ -112,12 +140,12 @@ impl LLM for ChatGPT {
                     for captures in re.captures_iter(chunk) {
                         if let Some(data_json) = captures.get(1) {
                             if terminate_response_signal.load(Ordering::Relaxed) {
-                                sender.send(Event::LLMEvent(LLMAnswer::EndAnswer))?;
+                                sender.send(LLMAnswer::EndAnswer)?;
                                 return Ok(());
                             }
 
                             if data_json.as_str() == "[DONE]" {
-                                sender.send(Event::LLMEvent(LLMAnswer::EndAnswer))?;
+                                sender.send(LLMAnswer::EndAnswer)?;
                                 return Ok(());
                             }
 
@@ -126,10 +154,23 @@ impl LLM for ChatGPT {
                             let msg = answer["choices"][0]["delta"]["content"].as_str();
 
                             if let Some(msg) = msg {
-                                sender.send(Event::LLMEvent(LLMAnswer::Answer(msg.to_string())))?;
+                                sender.send(LLMAnswer::Answer(msg.to_string()))?;
                             }
 
-                            sleep(Duration::from_millis(100)).await;
+                            // the last chunk has the token counts and no content
+                            if answer["usage"].is_object() {
+                                let stats = Stats {
+                                    model: Some(format!(
+                                        "chatgpt/{}",
+                                        answer["model"].as_str().unwrap_or(&self.model)
+                                    )),
+                                    prompt_tokens: answer["usage"]["prompt_tokens"].as_u64(),
+                                    completion_tokens: answer["usage"]["completion_tokens"]
+                                        .as_u64(),
+                                    generation_time: None,
+                                };
+                                sender.send(LLMAnswer::Stats(stats))?;
+                            }
                         }
                     }
                 }
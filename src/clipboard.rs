@@ -0,0 +1,53 @@
+use std::io::{self, Write};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::{app::AppResult, config::ClipboardBackend};
+
+pub enum Clipboard {
+    // X11, Wayland, macOS or Windows clipboard
+    System(arboard::Clipboard),
+    // Set the clipboard of the terminal with the OSC 52 escape sequence.
+    // Works over SSH, but the clipboard can not be read.
+    Osc52,
+}
+
+impl Clipboard {
+    pub fn new(backend: &ClipboardBackend) -> Option<Self> {
+        match backend {
+            ClipboardBackend::Auto => Some(
+                arboard::Clipboard::new()
+                    .map(Self::System)
+                    .unwrap_or(Self::Osc52),
+            ),
+            ClipboardBackend::System => arboard::Clipboard::new().ok().map(Self::System),
+            ClipboardBackend::Osc52 => Some(Self::Osc52),
+        }
+    }
+
+    pub fn set_text(&mut self, text: String) -> AppResult<()> {
+        match self {
+            Self::System(clipboard) => clipboard.set_text(text)?,
+            Self::Osc52 => {
+                let mut sequence = format!("\x1b]52;c;{}\x07", STANDARD.encode(text));
+
+                // tmux only forwards the sequence to the terminal when it is wrapped
+                if std::env::var("TMUX").is_ok() {
+                    sequence = format!("\x1bPtmux;\x1b{}\x1b\\", sequence);
+                }
+
+                let mut stdout = io::stdout();
+                stdout.write_all(sequence.as_bytes())?;
+                stdout.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_text(&mut self) -> Option<String> {
+        match self {
+            Self::System(clipboard) => clipboard.get_text().ok(),
+            Self::Osc52 => None,
+        }
+    }
+}
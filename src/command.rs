@@ -0,0 +1,287 @@
+use std::path::PathBuf;
+
+/// Name, arguments and description of the commands, for the completion and the help
+pub const COMMANDS: [(&str, &str, &str); 8] = [
+    ("model", "<backend/model>", "Switch the model of the chat"),
+    ("system", "<prompt>", "Add a system prompt to the chat"),
+    (
+        "clear",
+        "",
+        "Start new chat and save the previous one to the history",
+    ),
+    ("save", "", "Save the chat to the history"),
+    ("export", "<file>", "Export the chat to a markdown file"),
+    ("temp", "<temperature>", "Set the temperature, from 0 to 2"),
+    (
+        "retry",
+        "",
+        "Regenerate the last answer, or resend a prompt that failed",
+    ),
+    ("help", "", "Show help"),
+];
+
+/// A prompt starting with `/`, run instead of being sent
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Model(String),
+    System(String),
+    Clear,
+    Save,
+    Export(PathBuf),
+    Temperature(f64),
+    Retry,
+    Help,
+}
+
+fn usage(name: &str) -> String {
+    match COMMANDS.iter().find(|command| command.0 == name) {
+        Some((_, "", _)) => format!("`/{}` takes no argument", name),
+        Some((_, args, _)) => format!("Usage: /{} {}", name, args),
+        None => String::new(),
+    }
+}
+
+impl Command {
+    /// Parse a prompt starting with `/`. Returns `None` for the other prompts,
+    /// and for the ones starting with `//` which are sent without the first `/`.
+    pub fn parse(input: &str) -> Option<Result<Self, String>> {
+        let input = input.strip_prefix('/')?;
+        if input.starts_with('/') {
+            return None;
+        }
+
+        let (name, args) = match input.split_once(char::is_whitespace) {
+            Some((name, args)) => (name, args.trim()),
+            None => (input, ""),
+        };
+
+        let command = match (name, args) {
+            ("model" | "system" | "export" | "temp", "") => Err(usage(name)),
+            ("clear" | "save" | "retry" | "help", args) if !args.is_empty() => Err(usage(name)),
+
+            ("model", model) => Ok(Command::Model(model.to_string())),
+            ("system", prompt) => Ok(Command::System(prompt.to_string())),
+            ("clear", _) => Ok(Command::Clear),
+            ("save", _) => Ok(Command::Save),
+            ("export", path) => Ok(Command::Export(expand_home(path))),
+            ("temp", temperature) => temperature
+                .parse::<f64>()
+                .ok()
+                .filter(|temperature| (0.0..=2.0).contains(temperature))
+                .map(Command::Temperature)
+                .ok_or_else(|| "The temperature is a number from 0 to 2".to_string()),
+            ("retry", _) => Ok(Command::Retry),
+            ("help", _) => Ok(Command::Help),
+
+            _ => Err(format!(
+                "Unknown command `/{}`, start with `//` to send it as a message",
+                name
+            )),
+        };
+
+        Some(command)
+    }
+}
+
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(path), Some(home)) => home.join(path),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Completion of a command being typed
+#[derive(Debug, Default, PartialEq)]
+pub struct Completion {
+    /// The input completed up to the common prefix of the candidates
+    pub text: String,
+    /// Shown when there are several candidates
+    pub candidates: Vec<String>,
+}
+
+pub fn common_prefix(candidates: &[String]) -> String {
+    let mut prefix = candidates.first().cloned().unwrap_or_default();
+    for candidate in candidates {
+        while !candidate.starts_with(&prefix) {
+            prefix.pop();
+        }
+    }
+    prefix
+}
+
+/// Complete the name of a command, or the model of `/model`, from the models of the config
+pub fn complete(input: &str, models: &[String]) -> Option<Completion> {
+    let input = input.strip_prefix('/')?;
+
+    let (prefix, candidates, after) = match input.split_once(' ') {
+        Some(("model", model)) => (
+            "/model ",
+            models
+                .iter()
+                .filter(|name| name.starts_with(model))
+                .cloned()
+                .collect::<Vec<_>>(),
+            "",
+        ),
+        Some(_) => return None,
+        None => {
+            let candidates: Vec<String> = COMMANDS
+                .iter()
+                .filter(|command| command.0.starts_with(input))
+                .map(|command| command.0.to_string())
+                .collect();
+
+            // commands with arguments are followed by a space
+            let after = match &candidates[..] {
+                [name]
+                    if COMMANDS
+                        .iter()
+                        .any(|command| command.0 == name && !command.1.is_empty()) =>
+                {
+                    " "
+                }
+                _ => "",
+            };
+            ("/", candidates, after)
+        }
+    };
+
+    match candidates.len() {
+        0 => None,
+        1 => Some(Completion {
+            text: format!("{}{}{}", prefix, candidates[0], after),
+            candidates: Vec::new(),
+        }),
+        _ => Some(Completion {
+            text: format!("{}{}", prefix, common_prefix(&candidates)),
+            candidates: match prefix {
+                "/" => candidates
+                    .iter()
+                    .map(|candidate| format!("/{}", candidate))
+                    .collect(),
+                _ => candidates,
+            },
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(Command::parse("hello"), None);
+        assert_eq!(Command::parse("//model is sent"), None);
+        assert_eq!(Command::parse("/clear"), Some(Ok(Command::Clear)));
+        assert_eq!(
+            Command::parse("/model  ollama/llama3 "),
+            Some(Ok(Command::Model("ollama/llama3".to_string())))
+        );
+        assert_eq!(
+            Command::parse("/system be brief"),
+            Some(Ok(Command::System("be brief".to_string())))
+        );
+    }
+
+    #[test]
+    fn parse_usage_errors() {
+        assert_eq!(
+            Command::parse("/model"),
+            Some(Err("Usage: /model <backend/model>".to_string()))
+        );
+        assert_eq!(
+            Command::parse("/save now"),
+            Some(Err("`/save` takes no argument".to_string()))
+        );
+        assert_eq!(
+            Command::parse("/nope"),
+            Some(Err(
+                "Unknown command `/nope`, start with `//` to send it as a message".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_temperature() {
+        assert_eq!(
+            Command::parse("/temp 0"),
+            Some(Ok(Command::Temperature(0.0)))
+        );
+        assert_eq!(
+            Command::parse("/temp 2"),
+            Some(Ok(Command::Temperature(2.0)))
+        );
+
+        let error = Some(Err("The temperature is a number from 0 to 2".to_string()));
+        assert_eq!(Command::parse("/temp 2.1"), error);
+        assert_eq!(Command::parse("/temp -1"), error);
+        assert_eq!(Command::parse("/temp hot"), error);
+    }
+
+    #[test]
+    fn complete_commands() {
+        assert_eq!(complete("hello", &[]), None);
+        assert_eq!(complete("/x", &[]), None);
+        assert_eq!(complete("/save ", &[]), None);
+
+        // the commands with arguments are followed by a space
+        assert_eq!(
+            complete("/te", &[]),
+            Some(Completion {
+                text: "/temp ".to_string(),
+                candidates: Vec::new(),
+            })
+        );
+        assert_eq!(
+            complete("/cl", &[]),
+            Some(Completion {
+                text: "/clear".to_string(),
+                candidates: Vec::new(),
+            })
+        );
+
+        assert_eq!(
+            complete("/s", &[]),
+            Some(Completion {
+                text: "/s".to_string(),
+                candidates: vec!["/system".to_string(), "/save".to_string()],
+            })
+        );
+    }
+
+    #[test]
+    fn complete_models() {
+        let models = [
+            "ollama/llama3".to_string(),
+            "ollama/llava".to_string(),
+            "chatgpt/gpt-4o".to_string(),
+        ];
+
+        assert_eq!(
+            complete("/model o", &models),
+            Some(Completion {
+                text: "/model ollama/lla".to_string(),
+                candidates: vec!["ollama/llama3".to_string(), "ollama/llava".to_string()],
+            })
+        );
+        assert_eq!(
+            complete("/model c", &models),
+            Some(Completion {
+                text: "/model chatgpt/gpt-4o".to_string(),
+                candidates: Vec::new(),
+            })
+        );
+        assert_eq!(complete("/model mistral", &models), None);
+    }
+
+    #[test]
+    fn common_prefix_of_candidates() {
+        assert_eq!(common_prefix(&[]), "");
+        assert_eq!(
+            common_prefix(&["system".to_string(), "save".to_string()]),
+            "s"
+        );
+        assert_eq!(common_prefix(&["help".to_string()]), "help");
+    }
+}
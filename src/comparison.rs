@@ -0,0 +1,205 @@
+use std::sync::{atomic::AtomicBool, Arc};
+
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Style, Stylize},
+    text::{Line, Span, Text},
+    widgets::{Block, Paragraph, Wrap},
+    Frame,
+};
+
+use crate::{
+    chat::{Alternative, Answer},
+    formatter::Formatter,
+    keymap::{Action, Keymap},
+    llm::LLMAnswer,
+    theme::Theme,
+};
+
+/// Answers of several models to the same prompt, streamed side by side
+#[derive(Debug, Clone)]
+pub struct Comparison<'a> {
+    pub columns: Vec<Column<'a>>,
+    pub selected: usize,
+    pub scroll: u16,
+    // stops the streams of all the columns
+    pub terminate_response_signal: Arc<AtomicBool>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Column<'a> {
+    // id of the stream of the answer
+    pub stream: usize,
+    // name of the model in the `compare` setting, e.g. `ollama/llama3`
+    pub model: String,
+    pub answer: Answer<'a>,
+    pub streaming: bool,
+    // the request failed, the error is shown after the answer
+    pub error: Option<String>,
+}
+
+impl Column<'_> {
+    pub fn new(stream: usize, model: String) -> Self {
+        Self {
+            stream,
+            model,
+            answer: Answer::requested(),
+            streaming: true,
+            error: None,
+        }
+    }
+}
+
+impl<'a> Comparison<'a> {
+    pub fn new(columns: Vec<Column<'a>>, terminate_response_signal: Arc<AtomicBool>) -> Self {
+        Self {
+            columns,
+            selected: 0,
+            scroll: 0,
+            terminate_response_signal,
+        }
+    }
+
+    pub fn has_stream(&self, stream: usize) -> bool {
+        self.columns.iter().any(|column| column.stream == stream)
+    }
+
+    pub fn handle_answer(&mut self, stream: usize, event: LLMAnswer, formatter: &Formatter) {
+        let Some(column) = self
+            .columns
+            .iter_mut()
+            .find(|column| column.stream == stream)
+        else {
+            return;
+        };
+
+        match event {
+            LLMAnswer::StartAnswer => {}
+            LLMAnswer::Answer(answer) => column.answer.push(&answer, formatter),
+            LLMAnswer::Stats(stats) => column.answer.set_stats(stats),
+            LLMAnswer::EndAnswer => {
+                column.answer.finish();
+                column.streaming = false;
+            }
+            LLMAnswer::Error(error) => {
+                column.answer.finish();
+                column.error = Some(error);
+                column.streaming = false;
+            }
+        }
+    }
+
+    pub fn selected_answer(&self) -> &str {
+        &self.columns[self.selected].answer.plain_answer
+    }
+
+    pub fn streaming(&self) -> bool {
+        self.columns.iter().any(|column| column.streaming)
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1).min(self.columns.len() - 1);
+    }
+
+    /// The selected answer, the other ones are kept as its alternatives
+    pub fn pick(self) -> Answer<'a> {
+        let mut columns = self.columns;
+        let mut answer = columns.remove(self.selected).answer;
+
+        answer.alternatives = columns
+            .into_iter()
+            .filter(|column| !column.answer.plain_answer.is_empty())
+            .map(|mut column| Alternative {
+                metadata: column.answer.metadata(),
+                content: column.answer.plain_answer,
+            })
+            .collect();
+
+        answer
+    }
+
+    pub fn render(&mut self, frame: &mut Frame, area: Rect, theme: &Theme, keymap: &Keymap) {
+        let [columns_area, hint_area] =
+            Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(area);
+
+        let areas =
+            Layout::horizontal(vec![Constraint::Fill(1); self.columns.len()]).split(columns_area);
+
+        // the columns are scrolled together, up to the end of the longest answer
+        let max_scroll = self
+            .columns
+            .iter()
+            .zip(areas.iter())
+            .map(|(column, area)| {
+                column
+                    .answer
+                    .height(area.width.saturating_sub(2))
+                    .saturating_sub(area.height.saturating_sub(2).into())
+            })
+            .max()
+            .unwrap_or_default();
+        self.scroll = self.scroll.min(max_scroll.try_into().unwrap_or(u16::MAX));
+
+        for (index, (column, area)) in self.columns.iter().zip(areas.iter()).enumerate() {
+            let status = match (column.streaming, &column.error) {
+                (true, _) => Span::raw(" ● ").fg(theme.warning),
+                (false, Some(_)) => Span::raw(" ✗ ").fg(theme.error),
+                (false, None) => Span::raw(" ✓ ").fg(theme.info),
+            };
+
+            let (border_type, border_style) = match index == self.selected {
+                true => (theme.focused_border, Style::default().fg(theme.focus)),
+                false => (theme.border, Style::default()),
+            };
+
+            let mut text = if column.answer.plain_answer.is_empty() && column.streaming {
+                Text::from(Line::raw("🤖: Waiting …"))
+            } else {
+                column.answer.formatted_answer.clone()
+            };
+            if let Some(error) = &column.error {
+                text.push_line(Line::from(error.as_str()).fg(theme.error));
+            }
+
+            let paragraph = Paragraph::new(text)
+                .wrap(Wrap { trim: false })
+                .scroll((self.scroll, 0))
+                .block(
+                    Block::bordered()
+                        .title(Line::from(vec![
+                            status,
+                            Span::raw(format!("{} ", column.model)),
+                        ]))
+                        .border_type(border_type)
+                        .border_style(border_style),
+                );
+
+            frame.render_widget(paragraph, *area);
+        }
+
+        let select = format!(
+            "{} or {}: select an answer",
+            keymap.keys(Action::SelectPreviousAnswer),
+            keymap.keys(Action::SelectNextAnswer)
+        );
+        let hint = match self.streaming() {
+            true => format!(
+                "{} · {}: stop the streams · {}: discard",
+                select,
+                keymap.keys(Action::StopStream),
+                keymap.keys(Action::Dismiss)
+            ),
+            false => format!(
+                "{} · {}: keep it in the chat · {}: discard",
+                select,
+                keymap.keys(Action::KeepAnswer),
+                keymap.keys(Action::Dismiss)
+            ),
+        };
+        frame.render_widget(Paragraph::new(Line::from(hint).fg(theme.dim)), hint_area);
+    }
+}
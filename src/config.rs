@@ -1,8 +1,11 @@
+use crate::keymap::{Action, Bindings, Keymap};
 use crate::llm::LLMBackend;
+use crate::theme::Theme;
 use toml;
 
 use dirs;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 #[derive(Deserialize, Debug)]
@@ -10,9 +13,36 @@ pub struct Config {
     #[serde(default)]
     pub key_bindings: KeyBindings,
 
+    // the `[keymap]` section, the effective bindings are in `keymap`
+    #[serde(default, rename = "keymap")]
+    keymap_config: HashMap<Action, Bindings>,
+
+    #[serde(skip)]
+    pub keymap: Keymap,
+
     #[serde(default = "default_llm_backend")]
     pub llm: LLMBackend,
 
+    #[serde(default)]
+    pub clipboard: ClipboardBackend,
+
+    #[serde(default)]
+    pub renderer: MarkdownRenderer,
+
+    #[serde(default = "default_mouse")]
+    pub mouse: bool,
+
+    // size limit of the files attached to a prompt, in KB
+    #[serde(default = "default_max_attachment_size")]
+    pub max_attachment_size: usize,
+
+    // models answering side by side, e.g. `["ollama/llama3", "chatgpt/gpt-4o"]`
+    #[serde(default)]
+    pub compare: Vec<String>,
+
+    #[serde(default)]
+    pub theme: Theme,
+
     #[serde(default)]
     pub chatgpt: ChatGPTConfig,
 
@@ -25,6 +55,34 @@ pub fn default_llm_backend() -> LLMBackend {
     LLMBackend::ChatGPT
 }
 
+pub fn default_mouse() -> bool {
+    true
+}
+
+pub fn default_max_attachment_size() -> usize {
+    100
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipboardBackend {
+    // system clipboard, or OSC 52 when it is not available
+    #[default]
+    Auto,
+    System,
+    Osc52,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MarkdownRenderer {
+    // headings, lists, tables and code blocks rendered by tenere
+    #[default]
+    Native,
+    // markdown source highlighted by bat
+    Bat,
+}
+
 // ChatGPT
 #[derive(Deserialize, Debug, Clone)]
 pub struct ChatGPTConfig {
@@ -35,6 +93,9 @@ pub struct ChatGPTConfig {
 
     #[serde(default = "ChatGPTConfig::default_url")]
     pub url: String,
+
+    // number of tokens of the context window of the model
+    pub context_window: Option<usize>,
 }
 
 impl Default for ChatGPTConfig {
@@ -43,6 +104,7 @@ impl Default for ChatGPTConfig {
             openai_api_key: None,
             model: Self::default_model(),
             url: Self::default_url(),
+            context_window: None,
         }
     }
 }
@@ -63,6 +125,7 @@ impl ChatGPTConfig {
 pub struct LLamacppConfig {
     pub url: String,
     pub api_key: Option<String>,
+    pub context_window: Option<usize>,
 }
 
 // Ollama
@@ -71,6 +134,7 @@ pub struct LLamacppConfig {
 pub struct OllamaConfig {
     pub url: String,
     pub model: String,
+    pub context_window: Option<usize>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -118,6 +182,56 @@ impl KeyBindings {
 }
 
 impl Config {
+    /// Backend and model used for the chat, e.g. `ollama/llama3`
+    pub fn model(&self) -> String {
+        match self.llm {
+            LLMBackend::ChatGPT => format!("chatgpt/{}", self.chatgpt.model),
+            LLMBackend::LLamacpp => "llamacpp".to_string(),
+            LLMBackend::Ollama => match &self.ollama {
+                Some(ollama) => format!("ollama/{}", ollama.model),
+                None => "ollama".to_string(),
+            },
+        }
+    }
+
+    /// Context window of the backend of a model like `ollama/llama3`
+    pub fn context_window(&self, model: &str) -> Option<usize> {
+        match model.split('/').next()? {
+            "chatgpt" => self.chatgpt.context_window,
+            "llamacpp" => self.llamacpp.as_ref()?.context_window,
+            "ollama" => self.ollama.as_ref()?.context_window,
+            _ => None,
+        }
+    }
+
+    /// Name of a model given as `backend/model`, or as a backend alone with the model of its config section
+    pub fn model_name(&self, name: &str) -> String {
+        match (name, &self.ollama) {
+            ("chatgpt", _) => format!("chatgpt/{}", self.chatgpt.model),
+            ("ollama", Some(ollama)) => format!("ollama/{}", ollama.model),
+            _ => name.to_string(),
+        }
+    }
+
+    /// The models of the config sections and of `compare`, to complete `/model`
+    pub fn models(&self) -> Vec<String> {
+        let mut models = vec![self.model_name("chatgpt")];
+        if self.llamacpp.is_some() {
+            models.push("llamacpp".to_string());
+        }
+        if self.ollama.is_some() {
+            models.push(self.model_name("ollama"));
+        }
+
+        for name in &self.compare {
+            let name = self.model_name(name);
+            if !models.contains(&name) {
+                models.push(name);
+            }
+        }
+        models
+    }
+
     pub fn load(custom_path: Option<PathBuf>) -> Self {
         let conf_path = if let Some(path) = custom_path {
             path
@@ -129,7 +243,15 @@ impl Config {
         };
 
         let config = std::fs::read_to_string(conf_path).unwrap_or_default();
-        let app_config: Config = toml::from_str(&config).unwrap();
+        let mut app_config: Config = toml::from_str(&config).unwrap();
+
+        app_config.keymap = match Keymap::new(&app_config.key_bindings, &app_config.keymap_config) {
+            Ok(keymap) => keymap,
+            Err(e) => {
+                eprintln!("Invalid keymap: {}", e);
+                std::process::exit(1)
+            }
+        };
 
         if app_config.llm == LLMBackend::LLamacpp && app_config.llamacpp.is_none() {
             eprintln!("Config for LLamacpp is not provided");
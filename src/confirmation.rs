@@ -0,0 +1,91 @@
+use ratatui::{
+    layout::{Alignment, Constraint, Direction, Layout},
+    style::{Style, Stylize},
+    text::{Line, Text},
+    widgets::{Block, Borders, Clear, Padding, Paragraph, Wrap},
+    Frame,
+};
+
+use crate::{
+    history::Archive,
+    keymap::{Action, Keymap},
+    shell::ShellCommand,
+    theme::Theme,
+};
+
+#[derive(Debug, Clone)]
+pub enum ConfirmationAction {
+    // restore the chats autosaved by the previous session, one per tab
+    RestoreChat(Vec<Archive>),
+    // insert a large text pasted in the prompt
+    Paste(String),
+    // send a prompt with large files attached
+    SendPrompt(String),
+    // send a prompt with large files attached to the models of `compare`
+    Compare(String),
+    // run the commands of the prompt and add their output to it
+    RunCommands(Vec<ShellCommand>),
+}
+
+/// Pop-up asking the user to confirm an action, with `y` by default, or discard it with `n`
+#[derive(Debug, Clone)]
+pub struct Confirmation {
+    pub message: String,
+    pub action: ConfirmationAction,
+}
+
+impl Confirmation {
+    pub fn new(message: String, action: ConfirmationAction) -> Self {
+        Self { message, action }
+    }
+
+    pub fn render(&self, frame: &mut Frame, theme: &Theme, keymap: &Keymap) {
+        let mut text = Text::from(self.message.as_str());
+        text.extend(Text::raw("\n"));
+        text.push_line(Line::from(vec![
+            keymap.keys(Action::Confirm).bold().fg(theme.accent),
+            ": Yes   ".into(),
+            keymap.keys(Action::Cancel).bold().fg(theme.accent),
+            ": No".into(),
+        ]));
+
+        let width = std::cmp::min(frame.area().width.saturating_sub(4), 70);
+        // message lines + borders and padding
+        let height = text.height() as u16 + 4;
+
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Fill(1),
+                Constraint::Length(height),
+                Constraint::Fill(1),
+            ])
+            .split(frame.area());
+
+        let block = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Fill(1),
+                Constraint::Length(width),
+                Constraint::Fill(1),
+            ])
+            .split(layout[1])[1];
+
+        let confirmation = Paragraph::new(text)
+            .wrap(Wrap { trim: false })
+            .alignment(Alignment::Center)
+            .block(
+                Block::default()
+                    .padding(Padding::uniform(1))
+                    .title(" Confirmation ")
+                    .title_style(Style::default().bold().fg(theme.warning))
+                    .title_alignment(Alignment::Center)
+                    .borders(Borders::ALL)
+                    .border_type(theme.focused_border)
+                    .border_style(Style::default().fg(theme.warning)),
+            );
+
+        frame.render_widget(Clear, block);
+        frame.render_widget(confirmation, block);
+    }
+}
@@ -0,0 +1,84 @@
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::app::AppResult;
+
+// `$VISUAL`, then `$EDITOR`, with its arguments, e.g. `code --wait`
+fn editor_command() -> Vec<String> {
+    let editor = env::var("VISUAL")
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .or_else(|| env::var("EDITOR").ok())
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+
+    editor.split_whitespace().map(String::from).collect()
+}
+
+// A new file only readable by the user, it can not be a file or a link created by someone else
+fn create_file() -> io::Result<(PathBuf, File)> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.subsec_nanos())
+        .unwrap_or_default();
+
+    let mut attempt = 0;
+    loop {
+        // markdown, so that the editor highlights the code blocks
+        let path = env::temp_dir().join(format!(
+            "tenere-prompt-{}-{:x}.md",
+            std::process::id(),
+            nanos.wrapping_add(attempt)
+        ));
+
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => attempt += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Open the text in the editor of the user, and return it once edited.
+/// The terminal has to be released before.
+pub fn edit(text: &str) -> AppResult<String> {
+    let (path, mut file) = create_file()?;
+    if let Err(e) = file.write_all(text.as_bytes()) {
+        let _ = fs::remove_file(&path);
+        return Err(e.into());
+    }
+    drop(file);
+
+    let command = editor_command();
+    let status = Command::new(&command[0])
+        .args(&command[1..])
+        .arg(&path)
+        .status();
+
+    let edited = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+
+    let status = status.map_err(|e| format!("Can not run `{}`: {}", command[0], e))?;
+    if !status.success() {
+        return Err(format!("`{}` exited with {}", command[0], status).into());
+    }
+
+    // editors end the file with a new line
+    let mut edited = edited?;
+    if edited.ends_with('\n') {
+        edited.pop();
+    }
+
+    Ok(edited)
+}
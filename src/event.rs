@@ -3,6 +3,7 @@ use std::time::Duration;
 use crate::app::AppResult;
 use crate::llm::LLMAnswer;
 use crate::notification::Notification;
+use crate::shell::ShellCommand;
 use crossterm::event::{Event as CrosstermEvent, KeyEvent, MouseEvent};
 use futures::{FutureExt, StreamExt};
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
@@ -12,25 +13,42 @@ pub enum Event {
     Tick,
     Key(KeyEvent),
     Mouse(MouseEvent),
+    Paste(String),
     Resize(u16, u16),
-    LLMEvent(LLMAnswer),
+    // answer of the backend of a session
+    LLMEvent(usize, LLMAnswer),
+    // answer of a model of a comparison, tagged with the id of its stream
+    CompareEvent(usize, LLMAnswer),
     Notification(Notification),
+    // edit the prompt in the editor of the user
+    OpenEditor,
+    // output of the commands of the prompt
+    ShellOutput(Vec<(ShellCommand, String)>),
 }
 
-#[allow(dead_code)]
 #[derive(Debug)]
 pub struct EventHandler {
     pub sender: UnboundedSender<Event>,
     receiver: UnboundedReceiver<Event>,
     handler: tokio::task::JoinHandle<()>,
+    tick_rate: Duration,
 }
 
 impl EventHandler {
     pub fn new(tick_rate: u64) -> Self {
         let tick_rate = Duration::from_millis(tick_rate);
         let (sender, receiver) = unbounded_channel();
-        let _sender = sender.clone();
-        let handler = tokio::spawn(async move {
+        let handler = Self::spawn(tick_rate, sender.clone());
+        Self {
+            sender,
+            receiver,
+            handler,
+            tick_rate,
+        }
+    }
+
+    fn spawn(tick_rate: Duration, _sender: UnboundedSender<Event>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
             let mut reader = crossterm::event::EventStream::new();
             let mut tick = tokio::time::interval(tick_rate);
             loop {
@@ -57,18 +75,24 @@ impl EventHandler {
                       },
                       CrosstermEvent::FocusGained => {
                       },
-                      CrosstermEvent::Paste(_) => {
+                      CrosstermEvent::Paste(text) => {
+                        _sender.send(Event::Paste(text)).unwrap();
                       },
                     }
                   }
                 };
             }
-        });
-        Self {
-            sender,
-            receiver,
-            handler,
-        }
+        })
+    }
+
+    /// Stop reading the terminal, so that another program can read it
+    pub async fn pause(&mut self) {
+        self.handler.abort();
+        let _ = (&mut self.handler).await;
+    }
+
+    pub fn resume(&mut self) {
+        self.handler = Self::spawn(self.tick_rate, self.sender.clone());
     }
 
     pub async fn next(&mut self) -> AppResult<Event> {
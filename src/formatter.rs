@@ -3,19 +3,40 @@ use ansi_to_tui::IntoText;
 use bat::{assets::HighlightingAssets, config::Config, controller::Controller, Input};
 use ratatui::text::Text;
 
+use crate::{config::MarkdownRenderer, markdown::Markdown, theme::Theme};
+
 pub struct Formatter<'a> {
     controller: Controller<'a>,
+    markdown: Markdown<'a>,
+    renderer: MarkdownRenderer,
+    pub theme: &'a Theme,
 }
 
 impl<'a> Formatter<'a> {
-    pub fn new(config: &'a Config, assets: &'a HighlightingAssets) -> Self {
+    pub fn new(
+        config: &'a Config,
+        assets: &'a HighlightingAssets,
+        renderer: MarkdownRenderer,
+        theme: &'a Theme,
+    ) -> Self {
         let controller = Controller::new(config, assets);
-        Self { controller }
+        let markdown = Markdown::new(
+            assets.get_syntax_set().unwrap(),
+            assets.get_theme(&theme.syntax_theme),
+            theme,
+        );
+        Self {
+            controller,
+            markdown,
+            renderer,
+            theme,
+        }
     }
 
-    pub fn init() -> (Config<'static>, HighlightingAssets) {
+    pub fn init(theme: &Theme) -> (Config<'static>, HighlightingAssets) {
         let config = bat::config::Config {
             colored_output: true,
+            theme: theme.syntax_theme.clone(),
             ..Default::default()
         };
         let assets = bat::assets::HighlightingAssets::from_binary();
@@ -23,11 +44,16 @@ impl<'a> Formatter<'a> {
     }
 
     pub fn format(&self, input: &str) -> Text<'static> {
-        let mut buffer = String::new();
-        let input = Input::from_bytes(input.as_bytes()).name("text.md");
-        self.controller
-            .run(vec![input.into()], Some(&mut buffer))
-            .unwrap();
-        buffer.into_text().unwrap_or(Text::from(buffer))
+        match self.renderer {
+            MarkdownRenderer::Native => self.markdown.render(input),
+            MarkdownRenderer::Bat => {
+                let mut buffer = String::new();
+                let input = Input::from_bytes(input.as_bytes()).name("text.md");
+                self.controller
+                    .run(vec![input.into()], Some(&mut buffer))
+                    .unwrap();
+                buffer.into_text().unwrap_or(Text::from(buffer))
+            }
+        }
     }
 }
@@ -1,4 +1,13 @@
-use crate::llm::{LLMAnswer, LLMRole};
+use crate::attachment;
+use crate::chat::{code_blocks, Message};
+use crate::command::Command;
+use crate::comparison::{Column, Comparison};
+use crate::confirmation::{Confirmation, ConfirmationAction};
+use crate::history::Archive;
+use crate::keymap::{Action, Key};
+use crate::llm::{LLMAnswer, LLMModel, LLMRole};
+use crate::notification::{Notification, NotificationLevel};
+use crate::shell;
 use crate::{chat::Chat, prompt::Mode};
 
 use crate::{
@@ -7,14 +16,24 @@ use crate::{
 };
 
 use crate::llm::LLM;
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 
-use ratatui::text::Line;
+use ratatui::{layout::Position, text::Line};
 
+use std::fs;
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::Mutex;
 
-use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+// lines scrolled by a turn of the mouse wheel
+const MOUSE_SCROLL: u16 = 3;
+
+// pastes larger than this are confirmed first
+const LARGE_PASTE_LINES: usize = 100;
+const LARGE_PASTE_CHARS: usize = 10_000;
 
 pub async fn handle_key_events(
     key_event: KeyEvent,
@@ -22,110 +41,245 @@ pub async fn handle_key_events(
     llm: Arc<Mutex<Box<dyn LLM + 'static>>>,
     sender: UnboundedSender<Event>,
 ) -> AppResult<()> {
-    match key_event.code {
-        // Quit the app
-        KeyCode::Char('q') if app.prompt.mode != Mode::Insert => {
+    let key = Key::from(key_event);
+    let config = app.config.clone();
+    let keymap = &config.keymap;
+
+    if let FocusedBlock::Confirmation = app.focused_block {
+        let confirmed = keymap.is_bound(Action::Confirm, key);
+        if confirmed || keymap.is_bound(Action::Cancel, key) {
+            if let Some(confirmation) = app.confirmation.take() {
+                handle_confirmation(confirmation.action, confirmed, app, llm, sender).await;
+            }
+            app.focused_block = FocusedBlock::Prompt;
+        } else if !key.is_text() && keymap.is_bound(Action::Quit, key) {
             app.running = false;
         }
+        return Ok(());
+    }
+
+    // typing a search query in the chat
+    if app.focused_block == FocusedBlock::Chat
+        && app
+            .chat
+            .search
+            .as_ref()
+            .is_some_and(|search| search.editing)
+    {
+        if let Some(search) = app.chat.search.as_mut() {
+            match key_event.code {
+                KeyCode::Char(c) => {
+                    search.query.push(c);
+                    app.chat.update_search();
+                }
+                KeyCode::Backspace => {
+                    search.query.pop();
+                    app.chat.update_search();
+                }
+                KeyCode::Enter if !search.query.is_empty() => {
+                    search.editing = false;
+                }
+                KeyCode::Enter | KeyCode::Esc => {
+                    app.chat.search = None;
+                }
+                _ => {}
+            }
+        }
+        return Ok(());
+    }
+
+    // the characters typed in the prompt are not shortcuts
+    let action =
+        if app.focused_block == FocusedBlock::Prompt && app.prompt.is_typing() && key.is_text() {
+            app.pending_keys.clear();
+            None
+        } else {
+            let mut sequence = std::mem::take(&mut app.pending_keys);
+            sequence.push(key);
+
+            let available_action = |keys: &[Key]| {
+                keymap
+                    .actions(keys)
+                    .into_iter()
+                    .find(|action| available(*action, app))
+            };
 
-        KeyCode::Char('c') if key_event.modifiers == KeyModifiers::CONTROL => {
+            match available_action(&sequence) {
+                Some(action) => Some(action),
+                None => {
+                    let action = available_action(&[key]);
+                    app.pending_keys = keymap.pending(&sequence);
+                    action
+                }
+            }
+        };
+
+    match action {
+        Some(Action::Quit) => {
             app.running = false;
         }
 
         // Terminate the stream response
-        KeyCode::Char('t') if key_event.modifiers == KeyModifiers::CONTROL => {
+        Some(Action::StopStream) => {
             app.terminate_response_signal
                 .store(true, std::sync::atomic::Ordering::Relaxed);
+
+            if let Some(comparison) = &app.chat.comparison {
+                comparison
+                    .terminate_response_signal
+                    .store(true, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+
+        // Open a new chat in a tab
+        Some(Action::NewTab) => {
+            let llm = LLMModel::init(&app.config.llm, app.config.clone()).await;
+            app.new_tab(app.config.model(), llm);
+
+            app.focused_block = FocusedBlock::Prompt;
+            return Ok(());
         }
 
-        // scroll down
-        KeyCode::Char('j') | KeyCode::Down => match app.focused_block {
+        // Close the tab, its chat is saved to the history
+        Some(Action::CloseTab) => {
+            if let Some(mut session) = app.close_tab() {
+                session
+                    .terminate_response_signal
+                    .store(true, std::sync::atomic::Ordering::Relaxed);
+
+                // the answers of a comparison are dropped with the tab
+                if let Some(comparison) = &session.chat.comparison {
+                    comparison
+                        .terminate_response_signal
+                        .store(true, std::sync::atomic::Ordering::Relaxed);
+                }
+
+                // the part of the answer received so far is kept
+                if session.chat.streaming && !session.chat.answer.plain_answer.is_empty() {
+                    session
+                        .chat
+                        .handle_answer(LLMAnswer::EndAnswer, app.formatter);
+                }
+                app.history.archive_chat(&session.chat, sender.clone());
+            }
+            return Ok(());
+        }
+
+        Some(Action::NextTab) => {
+            app.switch_tab((app.tab + 1) % app.tab_count());
+        }
+
+        Some(Action::PreviousTab) => {
+            let count = app.tab_count();
+            app.switch_tab((app.tab + count - 1) % count);
+        }
+
+        // the terminal is released by the main loop
+        Some(Action::OpenEditor) => {
+            sender.send(Event::OpenEditor).unwrap();
+            return Ok(());
+        }
+
+        // the keys of the history are not passed to the prompt
+        Some(Action::PreviousPrompt) => {
+            app.prompt.recall_previous();
+            return Ok(());
+        }
+
+        Some(Action::NextPrompt) => {
+            app.prompt.recall_next();
+            return Ok(());
+        }
+
+        Some(Action::SearchPrompts) => {
+            app.prompt.search_history();
+            return Ok(());
+        }
+
+        Some(Action::Complete) => {
+            app.prompt.complete(&app.config.models());
+            return Ok(());
+        }
+
+        // Send the prompt to the models of the comparison
+        Some(Action::Compare) => {
+            compare(app, llm.clone(), sender.clone(), None).await;
+            return Ok(());
+        }
+
+        Some(Action::ScrollDown) => match app.focused_block {
             FocusedBlock::History => {
                 app.history.scroll_down();
             }
 
-            FocusedBlock::Chat => {
-                app.chat
-                    .automatic_scroll
-                    .store(false, std::sync::atomic::Ordering::Relaxed);
-                app.chat.scroll = app.chat.scroll.saturating_add(1);
-            }
+            FocusedBlock::Chat => match app.chat.comparison.as_mut() {
+                Some(comparison) => {
+                    comparison.scroll = comparison.scroll.saturating_add(1);
+                }
+                None => {
+                    app.chat
+                        .automatic_scroll
+                        .store(false, std::sync::atomic::Ordering::Relaxed);
+                    app.chat.scroll = app.chat.scroll.saturating_add(1);
+                }
+            },
 
             FocusedBlock::Preview => {
                 app.history.preview.scroll = app.history.preview.scroll.saturating_add(1);
             }
+
+            FocusedBlock::Help => app.help.scroll_down(),
             _ => (),
         },
 
-        // scroll up
-        KeyCode::Char('k') | KeyCode::Up => match app.focused_block {
+        Some(Action::ScrollUp) => match app.focused_block {
             FocusedBlock::History => app.history.scroll_up(),
 
             FocusedBlock::Preview => {
                 app.history.preview.scroll = app.history.preview.scroll.saturating_sub(1);
             }
 
-            FocusedBlock::Chat => {
-                app.chat
-                    .automatic_scroll
-                    .store(false, std::sync::atomic::Ordering::Relaxed);
-                app.chat.scroll = app.chat.scroll.saturating_sub(1);
-            }
+            FocusedBlock::Chat => match app.chat.comparison.as_mut() {
+                Some(comparison) => {
+                    comparison.scroll = comparison.scroll.saturating_sub(1);
+                }
+                None => {
+                    app.chat
+                        .automatic_scroll
+                        .store(false, std::sync::atomic::Ordering::Relaxed);
+                    app.chat.scroll = app.chat.scroll.saturating_sub(1);
+                }
+            },
 
+            FocusedBlock::Help => app.help.scroll_up(),
             _ => (),
         },
 
-        // `G`:  Mo to the bottom
-        KeyCode::Char('G') => match app.focused_block {
+        Some(Action::GoToBottom) => match app.focused_block {
             FocusedBlock::Chat => app.chat.move_to_bottom(),
             FocusedBlock::History => app.history.move_to_bottom(),
             _ => (),
         },
 
-        // `gg`: Move to the top
-        KeyCode::Char('g') => {
-            if app.previous_key == KeyCode::Char('g') {
-                match app.focused_block {
-                    FocusedBlock::Chat => {
-                        app.chat.move_to_top();
-                    }
-                    FocusedBlock::History => {
-                        app.history.move_to_top();
-                    }
-                    _ => (),
-                }
+        Some(Action::GoToTop) => match app.focused_block {
+            FocusedBlock::Chat => {
+                app.chat.move_to_top();
             }
-        }
+            FocusedBlock::History => {
+                app.history.move_to_top();
+            }
+            _ => (),
+        },
 
         // New chat
-        KeyCode::Char(c)
-            if c == app.config.key_bindings.new_chat
-                && key_event.modifiers == KeyModifiers::CONTROL =>
-        {
-            app.prompt.clear();
-
-            app.history
-                .preview
-                .text
-                .push(app.chat.formatted_chat.clone());
-
-            app.history.text.push(app.chat.plain_chat.clone());
-            // after adding to history, save the chat in file
-            app.history.save(app.history.text.len() - 1, sender.clone());
-
-            app.chat = Chat::default();
-
-            let llm = llm.clone();
-            {
-                let mut llm = llm.lock().await;
-                llm.clear();
+        Some(Action::NewChat) => {
+            if wait_for_answer(app, &sender) {
+                return Ok(());
             }
-
-            app.chat.scroll = 0;
+            new_chat(app, &llm, sender.clone()).await;
         }
 
-        // Switch the focus
-        KeyCode::Tab => match app.focused_block {
+        Some(Action::SwitchFocus) => match app.focused_block {
             FocusedBlock::Chat => {
                 app.focused_block = FocusedBlock::Prompt;
 
@@ -150,103 +304,1186 @@ pub async fn handle_key_events(
             _ => (),
         },
 
-        // Show help
-        KeyCode::Char(c)
-            if c == app.config.key_bindings.show_help && app.prompt.mode != Mode::Insert =>
-        {
+        Some(Action::ShowHelp) => {
             app.focused_block = FocusedBlock::Help;
             app.chat
                 .automatic_scroll
                 .store(true, std::sync::atomic::Ordering::Relaxed);
         }
 
-        // Show history
-        KeyCode::Char(c)
-            if c == app.config.key_bindings.show_history
-                && app.prompt.mode != Mode::Insert
-                && key_event.modifiers == KeyModifiers::CONTROL =>
-        {
+        Some(Action::ShowHistory) => {
             app.focused_block = FocusedBlock::History;
             app.chat
                 .automatic_scroll
                 .store(true, std::sync::atomic::Ordering::Relaxed);
         }
 
+        // Resume the selected chat from the history
+        Some(Action::ResumeChat) => {
+            // the answer being streamed belongs to the current chat
+            if wait_for_answer(app, &sender) {
+                return Ok(());
+            }
+
+            let file_name = app.history.selected().unwrap().file_name.clone();
+
+            // saving the current chat can change the order of the history
+            app.history.archive_chat(&app.chat, sender.clone());
+            let archive = app
+                .history
+                .archives
+                .iter()
+                .find(|archive| archive.file_name == file_name)
+                .cloned()
+                .unwrap();
+
+            load_chat(app, archive.messages, llm.clone()).await;
+            app.chat.archive = Some(archive.file_name);
+            app.chat.parent = archive.parent;
+
+            app.focused_block = FocusedBlock::Prompt;
+        }
+
+        Some(Action::SubmitPrompt) => {
+            if app.chat.comparison.is_some() {
+                let notif = Notification::new(
+                    "Keep or discard the answers of the comparison first".to_string(),
+                    NotificationLevel::Warning,
+                );
+                sender.send(Event::Notification(notif)).unwrap();
+                return Ok(());
+            }
+
+            let input = app.prompt.editor.lines().join("\n");
+            if let Some(command) = Command::parse(input.trim()) {
+                match command {
+                    Ok(command) => match run_command(command, app, &llm, sender.clone()).await {
+                        Ok(()) => {
+                            save_prompt(app, input.trim());
+                            app.prompt.clear();
+                        }
+                        Err(e) => app.prompt.notice = Some((NotificationLevel::Error, e)),
+                    },
+                    Err(e) => app.prompt.notice = Some((NotificationLevel::Error, e)),
+                }
+                return Ok(());
+            }
+
+            let Some(message) = prepare_prompt(app, ConfirmationAction::SendPrompt) else {
+                return Ok(());
+            };
+
+            push_prompt(app, &llm, message).await;
+            ask(app, llm.clone(), sender.clone());
+        }
+
+        Some(Action::SelectPreviousMessage) => {
+            app.chat.select_previous();
+        }
+
+        Some(Action::SelectNextMessage) => {
+            app.chat.select_next();
+        }
+
+        // Edit the selected message and resend it
+        Some(Action::EditMessage) => {
+            if wait_for_answer(app, &sender) {
+                return Ok(());
+            }
+
+            let index = app.chat.selected.unwrap();
+
+            app.prompt.clear();
+            app.prompt
+                .editor
+                .insert_str(&app.chat.messages[index].content);
+            app.prompt.editing = Some(index);
+
+            app.chat.selected = None;
+            app.chat
+                .automatic_scroll
+                .store(true, std::sync::atomic::Ordering::Relaxed);
+            app.focused_block = FocusedBlock::Prompt;
+        }
+
+        // Copy the selected message, or the last answer
+        Some(Action::CopyMessage) => {
+            if let Some(content) = app.chat.target_content() {
+                let text = content.to_string();
+                copy(app, text, "Message copied", sender.clone());
+            }
+        }
+
+        // Copy all the code blocks of the selected message, or the last answer
+        Some(Action::CopyCodeBlocks) => {
+            let blocks = app
+                .chat
+                .target_content()
+                .map(code_blocks)
+                .unwrap_or_default();
+
+            if blocks.is_empty() {
+                let notif =
+                    Notification::new("No code block".to_string(), NotificationLevel::Warning);
+                sender.send(Event::Notification(notif)).unwrap();
+            } else {
+                copy(
+                    app,
+                    blocks.join("\n\n"),
+                    "Code blocks copied",
+                    sender.clone(),
+                );
+            }
+        }
+
+        // Fork the conversation at the selected message
+        Some(Action::ForkChat) => {
+            if wait_for_answer(app, &sender) {
+                return Ok(());
+            }
+
+            let index = app.chat.selected.unwrap();
+            let message = app.chat.messages[index].clone();
+
+            // a user message is not part of the branch, it is loaded in the prompt instead
+            let end = match message.role {
+                LLMRole::USER => index,
+                _ => index + 1,
+            };
+            let messages = app.chat.messages[..end].to_vec();
+
+            let parent = app.history.archive_chat(&app.chat, sender.clone());
+
+            load_chat(app, messages, llm.clone()).await;
+            app.chat.parent = parent;
+            app.chat.archive = app.history.archive_chat(&app.chat, sender.clone());
+
+            if message.role == LLMRole::USER {
+                app.prompt.clear();
+                app.prompt.editor.insert_str(message.content);
+            }
+
+            app.chat
+                .automatic_scroll
+                .store(true, std::sync::atomic::Ordering::Relaxed);
+            app.focused_block = FocusedBlock::Prompt;
+        }
+
+        // Regenerate the last answer
+        Some(Action::Regenerate) => {
+            if wait_for_answer(app, &sender) {
+                return Ok(());
+            }
+
+            regenerate(app, &llm, sender.clone()).await;
+        }
+
+        // Switch to the previous or next response of the selected answer, or the last one
+        Some(action @ (Action::PreviousResponse | Action::NextResponse)) => {
+            let next = action == Action::NextResponse;
+            if let Some(index) = app.chat.cycle_alternatives(next, app.formatter) {
+                // the conversation of the backend goes on from the new response
+                let mut llm = llm.lock().await;
+                llm.truncate(index);
+                for message in &app.chat.messages[index..] {
+                    llm.append_chat_msg(message.content.clone(), message.role.clone());
+                }
+            }
+        }
+
+        Some(Action::Search) => {
+            app.chat.start_search();
+        }
+
+        Some(action @ (Action::NextMatch | Action::PreviousMatch)) => {
+            app.chat.next_match(action == Action::NextMatch);
+        }
+
+        Some(Action::SelectPreviousAnswer) => {
+            if let Some(comparison) = app.chat.comparison.as_mut() {
+                comparison.select_previous();
+            }
+        }
+
+        Some(Action::SelectNextAnswer) => {
+            if let Some(comparison) = app.chat.comparison.as_mut() {
+                comparison.select_next();
+            }
+        }
+
+        // Keep the selected answer of the comparison
+        Some(Action::KeepAnswer) => {
+            if app.chat.comparison.as_ref().is_some_and(|c| c.streaming()) {
+                let notif = Notification::new(
+                    "Wait for the answers to finish".to_string(),
+                    NotificationLevel::Warning,
+                );
+                sender.send(Event::Notification(notif)).unwrap();
+                return Ok(());
+            }
+
+            if app
+                .chat
+                .comparison
+                .as_ref()
+                .is_some_and(|comparison| comparison.selected_answer().is_empty())
+            {
+                let notif = Notification::new(
+                    "The answer is empty, select another one".to_string(),
+                    NotificationLevel::Warning,
+                );
+                sender.send(Event::Notification(notif)).unwrap();
+                return Ok(());
+            }
+
+            if let Some(content) = app.chat.keep_comparison(app.formatter) {
+                let mut llm = llm.lock().await;
+                llm.append_chat_msg(content, LLMRole::ASSISTANT);
+            }
+
+            app.chat
+                .automatic_scroll
+                .store(true, std::sync::atomic::Ordering::Relaxed);
+            app.focused_block = FocusedBlock::Prompt;
+        }
+
         // Discard help & history popups
-        KeyCode::Esc => match app.focused_block {
+        Some(Action::Dismiss) => match app.focused_block {
             FocusedBlock::History | FocusedBlock::Preview | FocusedBlock::Help => {
                 app.focused_block = FocusedBlock::Prompt
             }
+            // the prompt of the comparison goes back to the editor
+            FocusedBlock::Chat if app.chat.comparison.is_some() => {
+                discard_comparison(app, &llm).await;
+            }
+            FocusedBlock::Chat if app.chat.search.is_some() => {
+                app.chat.search = None;
+            }
+            FocusedBlock::Chat => {
+                app.chat.selected = None;
+            }
             _ => {}
         },
 
-        _ => {}
+        // Copy the nth code block of the selected message, or the last answer
+        Some(
+            action @ (Action::CopyCodeBlock1
+            | Action::CopyCodeBlock2
+            | Action::CopyCodeBlock3
+            | Action::CopyCodeBlock4
+            | Action::CopyCodeBlock5
+            | Action::CopyCodeBlock6
+            | Action::CopyCodeBlock7
+            | Action::CopyCodeBlock8
+            | Action::CopyCodeBlock9),
+        ) => {
+            let n = action.code_block().unwrap_or(1);
+            let block = app
+                .chat
+                .target_content()
+                .and_then(|content| code_blocks(content).into_iter().nth(n - 1));
+
+            match block {
+                Some(block) => copy(
+                    app,
+                    block,
+                    &format!("Code block {} copied", n),
+                    sender.clone(),
+                ),
+                None => {
+                    let notif = Notification::new(
+                        format!("No code block {}", n),
+                        NotificationLevel::Warning,
+                    );
+                    sender.send(Event::Notification(notif)).unwrap();
+                }
+            }
+        }
+
+        // the prompt leaves the other modes as with the Esc of vim
+        Some(Action::NormalMode) => {
+            app.prompt.handler(
+                KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE),
+                app.clipboard.as_mut(),
+            );
+            return Ok(());
+        }
+
+        // answered in the confirmation pop-up
+        Some(Action::Confirm | Action::Cancel) => {}
+
+        None => {}
     }
 
     if let FocusedBlock::Prompt = app.focused_block {
-        if let Mode::Normal = app.prompt.mode {
-            if key_event.code == KeyCode::Enter {
-                let user_input = app.prompt.editor.lines().join("\n");
-                let user_input = user_input.trim();
-                if user_input.is_empty() {
-                    return Ok(());
-                }
+        app.prompt.handler(key_event, app.clipboard.as_mut());
+    }
 
-                app.prompt.clear();
+    Ok(())
+}
 
-                app.chat.plain_chat.push(format!("👤 : {}\n", user_input));
+// Whether the action applies to the focused block and its state
+fn available(action: Action, app: &App) -> bool {
+    let focused = &app.focused_block;
+    let chat = *focused == FocusedBlock::Chat && app.chat.comparison.is_none();
+    let comparison = *focused == FocusedBlock::Chat && app.chat.comparison.is_some();
 
-                if app.chat.formatted_chat.width() == 0 {
-                    app.chat.formatted_chat = app
-                        .formatter
-                        .format(format!("👤: {}\n", user_input).as_str());
-                } else {
-                    app.chat.formatted_chat.extend(
-                        app.formatter
-                            .format(format!("👤: {}\n", user_input).as_str()),
-                    );
+    match action {
+        Action::Quit
+        | Action::StopStream
+        | Action::ShowHelp
+        | Action::NewTab
+        | Action::CloseTab
+        | Action::NextTab
+        | Action::PreviousTab => true,
+
+        // ctrl + n also recalls the next prompt
+        Action::NewChat => !(*focused == FocusedBlock::Prompt && app.prompt.history.is_active()),
+
+        // tab completes the commands and the paths typed in the prompt
+        Action::SwitchFocus => !(*focused == FocusedBlock::Prompt && app.prompt.can_complete()),
+
+        Action::Complete => *focused == FocusedBlock::Prompt && app.prompt.can_complete(),
+
+        // ctrl + h is a backspace in some terminals
+        Action::ShowHistory => !app.prompt.is_typing(),
+
+        Action::Dismiss => matches!(
+            focused,
+            FocusedBlock::History | FocusedBlock::Preview | FocusedBlock::Help | FocusedBlock::Chat
+        ),
+
+        Action::ScrollDown | Action::ScrollUp => matches!(
+            focused,
+            FocusedBlock::History | FocusedBlock::Preview | FocusedBlock::Help | FocusedBlock::Chat
+        ),
+
+        Action::GoToTop | Action::GoToBottom => chat || *focused == FocusedBlock::History,
+
+        Action::ResumeChat => *focused == FocusedBlock::History && app.history.selected().is_some(),
+
+        Action::SubmitPrompt => {
+            *focused == FocusedBlock::Prompt
+                && app.prompt.mode == Mode::Normal
+                && !app.prompt.is_typing()
+        }
+
+        Action::NormalMode => *focused == FocusedBlock::Prompt && app.prompt.mode != Mode::Normal,
+
+        Action::Compare | Action::OpenEditor => *focused == FocusedBlock::Prompt,
+
+        Action::PreviousPrompt | Action::NextPrompt => {
+            *focused == FocusedBlock::Prompt && app.prompt.can_recall()
+        }
+
+        // ctrl + r is the redo of the Normal mode
+        Action::SearchPrompts => {
+            *focused == FocusedBlock::Prompt
+                && (app.prompt.mode == Mode::Insert || app.prompt.history.is_searching())
+        }
+
+        Action::SelectPreviousMessage
+        | Action::SelectNextMessage
+        | Action::CopyMessage
+        | Action::CopyCodeBlocks
+        | Action::CopyCodeBlock1
+        | Action::CopyCodeBlock2
+        | Action::CopyCodeBlock3
+        | Action::CopyCodeBlock4
+        | Action::CopyCodeBlock5
+        | Action::CopyCodeBlock6
+        | Action::CopyCodeBlock7
+        | Action::CopyCodeBlock8
+        | Action::CopyCodeBlock9
+        | Action::Search => chat,
+
+        Action::EditMessage => {
+            chat && app
+                .chat
+                .selected_message()
+                .is_some_and(|message| message.role == LLMRole::USER)
+        }
+
+        Action::ForkChat => chat && app.chat.selected.is_some(),
+
+        // also the prompt of a failed answer
+        Action::Regenerate => {
+            chat && app
+                .chat
+                .messages
+                .last()
+                .is_some_and(|message| message.role != LLMRole::SYSTEM)
+        }
+
+        Action::PreviousResponse | Action::NextResponse => chat && !app.chat.streaming,
+
+        Action::NextMatch | Action::PreviousMatch => chat && app.chat.search.is_some(),
+
+        Action::SelectPreviousAnswer | Action::SelectNextAnswer | Action::KeepAnswer => comparison,
+
+        Action::Confirm | Action::Cancel => *focused == FocusedBlock::Confirmation,
+    }
+}
+
+// The answers of the comparison are dropped and its prompt goes back to the editor
+async fn discard_comparison(app: &mut App<'_>, llm: &Arc<Mutex<Box<dyn LLM + 'static>>>) {
+    if let Some(comparison) = app.chat.comparison.take() {
+        comparison
+            .terminate_response_signal
+            .store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+    app.chat.streaming = false;
+
+    let index = app.chat.messages.len() - 1;
+    let message = app.chat.messages[index].content.clone();
+    app.chat.truncate(index);
+    {
+        let mut llm = llm.lock().await;
+        llm.truncate(index);
+    }
+
+    app.prompt.clear();
+    app.prompt.editor.insert_str(message);
+    app.chat
+        .automatic_scroll
+        .store(true, std::sync::atomic::Ordering::Relaxed);
+    app.focused_block = FocusedBlock::Prompt;
+}
+
+pub fn handle_mouse_events(
+    mouse_event: MouseEvent,
+    app: &mut App<'_>,
+    sender: UnboundedSender<Event>,
+) {
+    let position = Position::new(mouse_event.column, mouse_event.row);
+
+    match app.focused_block {
+        FocusedBlock::History | FocusedBlock::Preview => match mouse_event.kind {
+            MouseEventKind::ScrollDown if app.history.list_area.contains(position) => {
+                app.history.scroll_down();
+            }
+
+            MouseEventKind::ScrollUp if app.history.list_area.contains(position) => {
+                app.history.scroll_up();
+            }
+
+            MouseEventKind::ScrollDown if app.history.preview_area.contains(position) => {
+                app.history.preview.scroll = app
+                    .history
+                    .preview
+                    .scroll
+                    .saturating_add(MOUSE_SCROLL.into());
+            }
+
+            MouseEventKind::ScrollUp if app.history.preview_area.contains(position) => {
+                app.history.preview.scroll = app
+                    .history
+                    .preview
+                    .scroll
+                    .saturating_sub(MOUSE_SCROLL.into());
+            }
+
+            MouseEventKind::Down(MouseButton::Left) if app.history.list_area.contains(position) => {
+                app.focused_block = FocusedBlock::History;
+                app.history.click(mouse_event.row);
+                app.history.preview.scroll = 0;
+            }
+
+            MouseEventKind::Down(MouseButton::Left)
+                if app.history.preview_area.contains(position) =>
+            {
+                app.focused_block = FocusedBlock::Preview;
+            }
+
+            _ => {}
+        },
+
+        FocusedBlock::Prompt | FocusedBlock::Chat => match mouse_event.kind {
+            MouseEventKind::ScrollDown if app.chat_area.contains(position) => {
+                app.chat
+                    .automatic_scroll
+                    .store(false, std::sync::atomic::Ordering::Relaxed);
+                app.chat.scroll = app.chat.scroll.saturating_add(MOUSE_SCROLL);
+            }
+
+            MouseEventKind::ScrollUp if app.chat_area.contains(position) => {
+                app.chat
+                    .automatic_scroll
+                    .store(false, std::sync::atomic::Ordering::Relaxed);
+                app.chat.scroll = app.chat.scroll.saturating_sub(MOUSE_SCROLL);
+            }
+
+            MouseEventKind::Down(MouseButton::Left) if app.chat_area.contains(position) => {
+                app.focused_block = FocusedBlock::Chat;
+                app.prompt.mode = Mode::Normal;
+                app.chat.start_drag(mouse_event.row - app.chat_area.y);
+            }
+
+            MouseEventKind::Drag(MouseButton::Left) => {
+                app.chat
+                    .drag(mouse_event.row.saturating_sub(app.chat_area.y));
+            }
+
+            MouseEventKind::Up(MouseButton::Left) => {
+                if let Some((text, lines)) = app.chat.end_drag() {
+                    let message = match lines {
+                        1 => "1 line copied".to_string(),
+                        n => format!("{} lines copied", n),
+                    };
+                    copy(app, text, &message, sender);
                 }
+            }
+
+            MouseEventKind::Down(MouseButton::Left) if app.prompt_area.contains(position) => {
+                app.focused_block = FocusedBlock::Prompt;
+                app.chat
+                    .automatic_scroll
+                    .store(true, std::sync::atomic::Ordering::Relaxed);
+            }
+
+            _ => {}
+        },
+
+        // the popups are handled with the keyboard
+        _ => {}
+    }
+}
+
+pub fn handle_paste(text: String, app: &mut App<'_>) {
+    if app.focused_block != FocusedBlock::Prompt {
+        return;
+    }
+
+    // terminals send the newlines as carriage returns
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+
+    let lines = text.lines().count();
+    let chars = text.chars().count();
+
+    if lines > LARGE_PASTE_LINES || chars > LARGE_PASTE_CHARS {
+        app.confirmation = Some(Confirmation::new(
+            format!(
+                "Paste {} lines ({} characters) in the prompt?",
+                lines, chars
+            ),
+            ConfirmationAction::Paste(text),
+        ));
+        app.focused_block = FocusedBlock::Confirmation;
+    } else {
+        app.prompt.paste(&text);
+    }
+}
+
+// The prompt is replaced by the text written in the editor
+pub fn handle_edited_prompt(
+    edited: AppResult<String>,
+    app: &mut App<'_>,
+    sender: UnboundedSender<Event>,
+) {
+    match edited {
+        Ok(text) => {
+            app.prompt.clear();
+            app.prompt.editor.insert_str(text);
+        }
+        Err(e) => {
+            let notif = Notification::new(
+                format!("Can not edit the prompt: {}", e),
+                NotificationLevel::Error,
+            );
+            sender.send(Event::Notification(notif)).unwrap();
+        }
+    }
+}
+
+// The commands of the prompt are replaced by their output, the prompt is then sent as usual
+pub fn handle_shell_output(outputs: Vec<(shell::ShellCommand, String)>, app: &mut App<'_>) {
+    let mut text = app.prompt.editor.lines().join("\n");
+    for (command, output) in outputs.iter().rev() {
+        text = shell::embed(&text, command, output);
+    }
+
+    app.prompt.clear();
+    app.prompt.editor.insert_str(text);
+    app.prompt.notice = Some((
+        NotificationLevel::Info,
+        "Output added to the prompt, press enter to send it".to_string(),
+    ));
+}
+
+// Copy the text to the clipboard. It is also available to paste in the prompt.
+fn copy(app: &mut App<'_>, text: String, message: &str, sender: UnboundedSender<Event>) {
+    app.prompt.editor.set_yank_text(text.clone());
+
+    let notif = match app
+        .clipboard
+        .as_mut()
+        .map(|clipboard| clipboard.set_text(text))
+    {
+        Some(Ok(_)) => Notification::new(message.to_string(), NotificationLevel::Info),
+        Some(Err(e)) => Notification::new(e.to_string(), NotificationLevel::Error),
+        None => Notification::new(
+            "The clipboard is not available, the text can only be pasted in the prompt".to_string(),
+            NotificationLevel::Warning,
+        ),
+    };
+    sender.send(Event::Notification(notif)).unwrap();
+}
+
+// Add the prompt to the history of the prompts sent
+fn save_prompt(app: &mut App<'_>, input: &str) {
+    if let Err(e) = app.prompt.history.push(input) {
+        app.notifications.push(Notification::new(
+            format!("Can not save the prompt to the history: {}", e),
+            NotificationLevel::Warning,
+        ));
+    }
+}
+
+// The message of the prompt, with the files of its `@path` references.
+// `None` when the prompt is empty, when it has shell commands to run, when a file can not be attached,
+// or when the files are too large and the user is asked to confirm with `confirm`.
+fn prepare_prompt(
+    app: &mut App<'_>,
+    confirm: impl FnOnce(String) -> ConfirmationAction,
+) -> Option<String> {
+    let text = app.prompt.editor.lines().join("\n");
+    let user_input = text.trim();
+    if user_input.is_empty() {
+        return None;
+    }
+
+    // `//` sends a message starting with `/` instead of a command
+    let user_input = user_input
+        .strip_prefix('/')
+        .filter(|input| input.starts_with('/'))
+        .unwrap_or(user_input);
+
+    // the commands are run first, their output is added to the prompt to review it
+    let commands = shell::commands(&text);
+    if !commands.is_empty() {
+        let list: Vec<String> = commands
+            .iter()
+            .map(|command| format!("$ {}", command.command))
+            .collect();
+        app.confirmation = Some(Confirmation::new(
+            format!(
+                "Run in the shell and add the output to the prompt?\n\n{}",
+                list.join("\n")
+            ),
+            ConfirmationAction::RunCommands(commands),
+        ));
+        app.focused_block = FocusedBlock::Confirmation;
+        return None;
+    }
+
+    // the references that are not files are sent as they are
+    let limit = app.config.max_attachment_size;
+    let attached = attachment::attach(&shell::unescape(user_input), limit * 1000);
+    for warning in &attached.warnings {
+        app.notifications.push(Notification::new(
+            warning.clone(),
+            NotificationLevel::Warning,
+        ));
+    }
+
+    if attached.size > limit * 1000 {
+        app.confirmation = Some(Confirmation::new(
+            format!(
+                "The attached files are {} KB, over the limit of {} KB. Send them anyway?",
+                attached.size.div_ceil(1000),
+                limit
+            ),
+            confirm(attached.text),
+        ));
+        app.focused_block = FocusedBlock::Confirmation;
+        return None;
+    }
+
+    Some(attached.text)
+}
+
+// Add the message to the chat, the prompt is saved to the history and cleared
+async fn push_prompt(
+    app: &mut App<'_>,
+    llm: &Arc<Mutex<Box<dyn LLM + 'static>>>,
+    user_input: String,
+) {
+    let prompt = app.prompt.editor.lines().join("\n");
+    save_prompt(app, prompt.trim());
+    app.prompt.clear();
+
+    // resend from an edited message: drop it and everything after it
+    if let Some(index) = app.prompt.editing.take() {
+        app.chat.truncate(index);
+
+        let mut llm = llm.lock().await;
+        llm.truncate(index);
+    }
+
+    app.chat
+        .push_user_message(user_input.clone(), app.formatter);
 
-                let llm = llm.clone();
+    let mut llm = llm.lock().await;
+    llm.append_chat_msg(user_input, LLMRole::USER);
+}
+
+// Save the chat to the history and start a new one
+async fn new_chat(
+    app: &mut App<'_>,
+    llm: &Arc<Mutex<Box<dyn LLM + 'static>>>,
+    sender: UnboundedSender<Event>,
+) {
+    app.prompt.clear();
+    app.prompt.editing = None;
+
+    app.history.archive_chat(&app.chat, sender);
+
+    app.chat = Chat::default();
+
+    {
+        let mut llm = llm.lock().await;
+        llm.clear();
+    }
+
+    app.chat.scroll = 0;
+}
+
+// Replace the last answer by a new one, the previous one is kept as an alternative
+async fn regenerate(
+    app: &mut App<'_>,
+    llm: &Arc<Mutex<Box<dyn LLM + 'static>>>,
+    sender: UnboundedSender<Event>,
+) {
+    // a prompt whose answer failed is sent again as it is
+    let answered = app
+        .chat
+        .messages
+        .last()
+        .is_some_and(|message| message.role == LLMRole::ASSISTANT);
+
+    if answered {
+        let index = app.chat.messages.len() - 1;
+        app.chat.regenerate();
+
+        let mut llm = llm.lock().await;
+        llm.truncate(index);
+    }
+
+    ask(app, llm.clone(), sender);
+}
+
+// The chat can not be changed while its answer is streamed, or compared
+fn streaming(app: &App<'_>) -> Result<(), String> {
+    if app.chat.streaming {
+        Err("Wait for the answer to finish".to_string())
+    } else if app.chat.comparison.is_some() {
+        Err("Keep or discard the answers of the comparison first".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+// Warn when the answer is streamed, the action is not done until it finishes
+fn wait_for_answer(app: &App<'_>, sender: &UnboundedSender<Event>) -> bool {
+    match streaming(app) {
+        Ok(()) => false,
+        Err(message) => {
+            let notif = Notification::new(message, NotificationLevel::Warning);
+            sender.send(Event::Notification(notif)).unwrap();
+            true
+        }
+    }
+}
+
+// Run a command typed in the prompt, the error is shown in the prompt
+async fn run_command(
+    command: Command,
+    app: &mut App<'_>,
+    llm: &Arc<Mutex<Box<dyn LLM + 'static>>>,
+    sender: UnboundedSender<Event>,
+) -> Result<(), String> {
+    let notify = |message: String| {
+        sender
+            .send(Event::Notification(Notification::new(
+                message,
+                NotificationLevel::Info,
+            )))
+            .unwrap();
+    };
+
+    match command {
+        // the conversation is sent to the new backend
+        Command::Model(name) => {
+            streaming(app)?;
+
+            let mut model = LLMModel::from_name(&name, &app.config)?;
+            for message in &app.chat.messages {
+                model.append_chat_msg(message.content.clone(), message.role.clone());
+            }
+            *llm.lock().await = model;
+
+            app.model = app.config.model_name(&name);
+            notify(format!("Switched to {}", app.model));
+        }
+
+        Command::System(prompt) => {
+            streaming(app)?;
+
+            app.chat.push_system_message(prompt.clone());
+            llm.lock().await.append_chat_msg(prompt, LLMRole::SYSTEM);
+            notify("System prompt added".to_string());
+        }
+
+        Command::Clear => {
+            streaming(app)?;
+            new_chat(app, llm, sender.clone()).await;
+        }
+
+        Command::Save => {
+            if app.chat.messages.is_empty() {
+                return Err("The chat is empty".to_string());
+            }
+            app.chat.archive = app.history.archive_chat(&app.chat, sender.clone());
+        }
+
+        Command::Export(path) => {
+            if app.chat.messages.is_empty() {
+                return Err("The chat is empty".to_string());
+            }
+
+            let markdown = Archive::new(app.chat.messages.clone()).markdown();
+            fs::write(&path, markdown)
+                .map_err(|e| format!("Can not export the chat to {}: {}", path.display(), e))?;
+            notify(format!("Chat exported to {}", path.display()));
+        }
+
+        // the backend is busy while it streams, the temperature is given with the next question
+        Command::Temperature(temperature) => {
+            app.temperature = Some(temperature);
+            notify(format!("Temperature set to {}", temperature));
+        }
+
+        Command::Retry => {
+            streaming(app)?;
+
+            if !app
+                .chat
+                .messages
+                .last()
+                .is_some_and(|message| message.role != LLMRole::SYSTEM)
+            {
+                return Err("There is no answer to regenerate".to_string());
+            }
+            regenerate(app, llm, sender.clone()).await;
+        }
+
+        Command::Help => {
+            app.focused_block = FocusedBlock::Help;
+        }
+    }
+
+    Ok(())
+}
+
+// Ask the backend to answer the conversation, the answer is streamed to the chat
+fn ask(app: &mut App<'_>, llm: Arc<Mutex<Box<dyn LLM + 'static>>>, sender: UnboundedSender<Event>) {
+    let session = app.session;
+
+    app.chat.streaming = true;
+    app.spinner.active = true;
+    app.chat.answer.requested = Some(Instant::now());
+
+    app.chat
+        .formatted_chat
+        .lines
+        .push(Line::raw("🤖: ".to_string()));
+
+    // tag the answers with the session, the tab can change during the stream
+    stream(
+        llm,
+        app.temperature,
+        app.terminate_response_signal.clone(),
+        sender,
+        move |answer| Event::LLMEvent(session, answer),
+    );
+}
+
+// Stream the answer of the backend as the events made by `event`
+fn stream(
+    llm: Arc<Mutex<Box<dyn LLM + 'static>>>,
+    temperature: Option<f64>,
+    terminate_response_signal: Arc<AtomicBool>,
+    sender: UnboundedSender<Event>,
+    event: impl Fn(LLMAnswer) -> Event + Send + 'static,
+) {
+    let (answer_sender, mut answer_receiver) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Some(answer) = answer_receiver.recv().await {
+            if sender.send(event(answer)).is_err() {
+                break;
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        let mut llm = llm.lock().await;
+        if let Some(temperature) = temperature {
+            llm.set_temperature(temperature);
+        }
+        let res = llm
+            .ask(answer_sender.clone(), terminate_response_signal)
+            .await;
+
+        if let Err(e) = res {
+            answer_sender.send(LLMAnswer::Error(e.to_string())).unwrap();
+        }
+    });
+}
+
+// Send the prompt to the models of the `compare` setting, their answers are streamed side by side
+async fn compare(
+    app: &mut App<'_>,
+    llm: Arc<Mutex<Box<dyn LLM + 'static>>>,
+    sender: UnboundedSender<Event>,
+    message: Option<String>,
+) {
+    let notify = |message: String, level: NotificationLevel| {
+        sender
+            .send(Event::Notification(Notification::new(message, level)))
+            .unwrap();
+    };
+
+    if app.config.compare.len() < 2 {
+        notify(
+            "Set at least two models in `compare` to compare their answers".to_string(),
+            NotificationLevel::Warning,
+        );
+        return;
+    }
+
+    if wait_for_answer(app, &sender) {
+        return;
+    }
+
+    let models = match app
+        .config
+        .compare
+        .iter()
+        .map(|name| LLMModel::from_name(name, &app.config))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(models) => models,
+        Err(e) => {
+            notify(e, NotificationLevel::Error);
+            return;
+        }
+    };
+
+    // the message is given once its large attachments are confirmed
+    let Some(message) = message.or_else(|| prepare_prompt(app, ConfirmationAction::Compare)) else {
+        return;
+    };
+    push_prompt(app, &llm, message).await;
+
+    // the comparison is stopped by its own signal, it can be discarded while streaming
+    let terminate_response_signal = Arc::new(AtomicBool::new(false));
+
+    let mut columns = Vec::new();
+    for (name, mut model) in app.config.compare.clone().into_iter().zip(models) {
+        for message in &app.chat.messages {
+            model.append_chat_msg(message.content.clone(), message.role.clone());
+        }
+
+        let id = app.new_id();
+        columns.push(Column::new(id, name));
+
+        stream(
+            Arc::new(Mutex::new(model)),
+            app.temperature,
+            terminate_response_signal.clone(),
+            sender.clone(),
+            move |answer| Event::CompareEvent(id, answer),
+        );
+    }
+
+    app.chat.comparison = Some(Comparison::new(columns, terminate_response_signal));
+    app.chat.streaming = true;
+
+    app.focused_block = FocusedBlock::Chat;
+    app.prompt.mode = Mode::Normal;
+}
+
+// Stream the answer to the chat of its session, in the active tab or in the background
+pub fn handle_compare_events(stream: usize, answer: LLMAnswer, app: &mut App<'_>) {
+    let formatter = app.formatter;
+
+    // the comparison can be in a tab in the background, or discarded
+    let chat = std::iter::once(&mut app.chat)
+        .chain(app.tabs.iter_mut().map(|tab| &mut tab.chat))
+        .find(|chat| {
+            chat.comparison
+                .as_ref()
+                .is_some_and(|comparison| comparison.has_stream(stream))
+        });
+
+    if let Some(chat) = chat {
+        let comparison = chat.comparison.as_mut().unwrap();
+        comparison.handle_answer(stream, answer, formatter);
+
+        // the chat is streaming until the last column ends
+        if !comparison.streaming() {
+            chat.streaming = false;
+        }
+    }
+}
+
+pub async fn handle_answer_events(session: usize, answer: LLMAnswer, app: &mut App<'_>) {
+    let formatter = app.formatter;
+
+    let (chat, llm, spinner, terminate_response_signal) = if session == app.session {
+        (
+            &mut app.chat,
+            app.llm.clone(),
+            &mut app.spinner,
+            app.terminate_response_signal.clone(),
+        )
+    } else {
+        match app.tabs.iter_mut().find(|tab| tab.id == session) {
+            Some(tab) => (
+                &mut tab.chat,
+                tab.llm.clone(),
+                &mut tab.spinner,
+                tab.terminate_response_signal.clone(),
+            ),
+            // the tab was closed
+            None => return,
+        }
+    };
+
+    match answer {
+        LLMAnswer::StartAnswer => {
+            spinner.active = false;
+            chat.handle_answer(answer, formatter);
+        }
+        LLMAnswer::EndAnswer => {
+            {
+                let mut llm = llm.lock().await;
+                llm.append_chat_msg(chat.answer.plain_answer.clone(), LLMRole::ASSISTANT);
+            }
+
+            chat.handle_answer(answer, formatter);
+            terminate_response_signal.store(false, std::sync::atomic::Ordering::Relaxed);
+        }
+        LLMAnswer::Error(_) => {
+            // the waiting line of an answer that did not start
+            if spinner.active {
+                spinner.active = false;
+                chat.handle_answer(LLMAnswer::StartAnswer, formatter);
+            }
+
+            // the beginning of the answer received before the error is kept
+            if !chat.answer.plain_answer.is_empty() {
                 {
                     let mut llm = llm.lock().await;
-                    llm.append_chat_msg(user_input.into(), LLMRole::USER);
+                    llm.append_chat_msg(chat.answer.plain_answer.clone(), LLMRole::ASSISTANT);
                 }
+                chat.handle_answer(LLMAnswer::EndAnswer, formatter);
+            }
 
-                app.spinner.active = true;
+            chat.handle_answer(answer, formatter);
+            terminate_response_signal.store(false, std::sync::atomic::Ordering::Relaxed);
+        }
+        _ => chat.handle_answer(answer, formatter),
+    }
+}
 
-                app.chat
-                    .formatted_chat
-                    .lines
-                    .push(Line::raw("🤖: ".to_string()));
+// Replace the current chat and the conversation of the backend with the messages
+async fn load_chat(
+    app: &mut App<'_>,
+    messages: Vec<Message>,
+    llm: Arc<Mutex<Box<dyn LLM + 'static>>>,
+) {
+    app.chat = Chat::from_messages(messages.clone(), app.formatter);
+    app.prompt.editing = None;
 
-                let terminate_response_signal = app.terminate_response_signal.clone();
+    let mut llm = llm.lock().await;
+    llm.clear();
+    for message in messages {
+        llm.append_chat_msg(message.content, message.role);
+    }
+}
+
+async fn handle_confirmation(
+    action: ConfirmationAction,
+    confirmed: bool,
+    app: &mut App<'_>,
+    llm: Arc<Mutex<Box<dyn LLM + 'static>>>,
+    sender: UnboundedSender<Event>,
+) {
+    match action {
+        ConfirmationAction::RestoreChat(archives) => {
+            if confirmed {
+                // the first chat in the active tab, the others in new tabs after it
+                let tab = app.tab;
+                for (index, archive) in archives.into_iter().enumerate() {
+                    let llm = if index == 0 {
+                        llm.clone()
+                    } else {
+                        let llm = LLMModel::init(&app.config.llm, app.config.clone()).await;
+                        app.new_tab(app.config.model(), llm);
+                        app.llm.clone()
+                    };
+                    load_chat(app, archive.messages, llm).await;
+                    app.chat.archive = Some(archive.file_name).filter(|name| !name.is_empty());
+                    app.chat.parent = archive.parent;
+                }
+                app.switch_tab(tab);
+            } else {
+                for archive in archives {
+                    let mut chat = Chat::from_messages(archive.messages, app.formatter);
+                    chat.archive = Some(archive.file_name).filter(|name| !name.is_empty());
+                    chat.parent = archive.parent;
+                    app.history.archive_chat(&chat, sender.clone());
+                }
 
-                let sender = sender.clone();
+                if let Err(e) = app.autosave.clear() {
+                    let notif = Notification::new(e.to_string(), NotificationLevel::Error);
+                    sender.send(Event::Notification(notif)).unwrap();
+                }
+            }
+        }
 
-                let llm = llm.clone();
+        ConfirmationAction::Paste(text) => {
+            if confirmed {
+                app.prompt.paste(&text);
+            }
+        }
+        ConfirmationAction::SendPrompt(message) => {
+            if confirmed {
+                push_prompt(app, &llm, message).await;
+                ask(app, llm, sender);
+            }
+        }
+        ConfirmationAction::Compare(message) => {
+            if confirmed {
+                compare(app, llm, sender, Some(message)).await;
+            }
+        }
+        ConfirmationAction::RunCommands(commands) => {
+            if confirmed {
+                app.prompt.notice =
+                    Some((NotificationLevel::Info, "Running the commands…".to_string()));
 
+                let max_size = app.config.max_attachment_size * 1000;
                 tokio::spawn(async move {
-                    let llm = llm.lock().await;
-                    let res = llm.ask(sender.clone(), terminate_response_signal).await;
-
-                    if let Err(e) = res {
-                        sender
-                            .send(Event::LLMEvent(LLMAnswer::StartAnswer))
-                            .unwrap();
-                        sender
-                            .send(Event::LLMEvent(LLMAnswer::Answer(e.to_string())))
-                            .unwrap();
+                    let mut outputs = Vec::new();
+                    for command in commands {
+                        let output = shell::run(&command.command, max_size).await;
+                        outputs.push((command, output));
                     }
+                    sender.send(Event::ShellOutput(outputs)).unwrap();
                 });
             }
         }
-
-        app.prompt
-            .handler(key_event, app.previous_key, app.clipboard.as_mut());
     }
-
-    app.previous_key = key_event.code;
-
-    Ok(())
 }
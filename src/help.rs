@@ -1,55 +1,56 @@
 use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout},
-    style::{Color, Style, Stylize},
-    widgets::{Block, BorderType, Borders, Cell, Clear, Padding, Row, Table},
+    style::{Style, Stylize},
+    widgets::{Block, Borders, Cell, Clear, Padding, Row, Table},
     Frame,
 };
 
+use crate::{command::COMMANDS, keymap::Keymap, theme::Theme};
+
 pub struct Help {
     block_height: usize,
-    keys: Vec<(Cell<'static>, &'static str)>,
+    scroll: usize,
+    keys: Vec<(String, &'static str)>,
 }
 
-impl Default for Help {
-    fn default() -> Self {
+impl Help {
+    /// The keys of the actions, from the keymap, and the commands of the prompt
+    pub fn new(keymap: &Keymap) -> Self {
+        let mut keys = keymap.help();
+        for (name, args, description) in COMMANDS {
+            keys.push((
+                format!("/{} {}", name, args).trim_end().to_string(),
+                description,
+            ));
+        }
+
         Self {
             block_height: 0,
-            keys: vec![
-                (
-                    Cell::from("Esc").bold().yellow(),
-                    "Switch to Normal mode / Dismiss pop-up",
-                ),
-                (Cell::from("Tab").bold().yellow(), "Switch the focus"),
-                (
-                    Cell::from("ctrl + n").bold().yellow(),
-                    "Start new chat and save the previous one to the history",
-                ),
-                (Cell::from("ctrl + h").bold().yellow(), "Show history"),
-                (
-                    Cell::from("ctrl + t").bold().yellow(),
-                    "Stop the stream response",
-                ),
-                (Cell::from("j or Down").bold().yellow(), "Scroll down"),
-                (Cell::from("k or Up").bold().yellow(), "Scroll up"),
-                (Cell::from("G").bold().yellow(), "Go to the end"),
-                (Cell::from("gg").bold().yellow(), "Go to the top"),
-                (Cell::from("?").bold().yellow(), "Show help"),
-            ],
+            scroll: 0,
+            keys,
         }
     }
-}
 
-impl Help {
-    pub fn new() -> Self {
-        Self::default()
+    // rows of keys visible in the pop-up
+    fn visible_rows(&self) -> usize {
+        self.block_height.saturating_sub(4)
     }
 
-    pub fn render(&mut self, frame: &mut Frame) {
+    pub fn scroll_down(&mut self) {
+        let max_scroll = self.keys.len().saturating_sub(self.visible_rows());
+        self.scroll = (self.scroll + 1).min(max_scroll);
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+
+    pub fn render(&mut self, frame: &mut Frame, theme: &Theme) {
         let layout = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
                 Constraint::Fill(1),
-                Constraint::Length(15),
+                Constraint::Length(self.keys.len() as u16 + 4),
                 Constraint::Fill(1),
             ])
             .flex(ratatui::layout::Flex::SpaceBetween)
@@ -59,20 +60,37 @@ impl Help {
             .direction(Direction::Horizontal)
             .constraints([
                 Constraint::Fill(1),
-                Constraint::Min(75),
+                Constraint::Min(85),
                 Constraint::Fill(1),
             ])
             .flex(ratatui::layout::Flex::SpaceBetween)
             .split(layout[1])[1];
 
         self.block_height = block.height as usize;
-        let widths = [Constraint::Length(12), Constraint::Fill(1)];
+        self.scroll = self
+            .scroll
+            .min(self.keys.len().saturating_sub(self.visible_rows()));
+
+        let keys_width = self
+            .keys
+            .iter()
+            .map(|key| key.0.chars().count())
+            .max()
+            .unwrap_or_default();
+        let widths = [
+            Constraint::Length(keys_width as u16 + 2),
+            Constraint::Fill(1),
+        ];
         let rows: Vec<Row> = self
             .keys
             .iter()
+            .skip(self.scroll)
             .map(|key| {
-                Row::new(vec![key.0.to_owned(), key.1.into()])
-                    .style(Style::default().fg(Color::White))
+                Row::new(vec![
+                    Cell::from(key.0.as_str()).bold().fg(theme.accent),
+                    key.1.into(),
+                ])
+                .style(Style::default().fg(theme.text))
             })
             .collect();
 
@@ -80,12 +98,12 @@ impl Help {
             Block::default()
                 .padding(Padding::uniform(1))
                 .title(" Help ")
-                .title_style(Style::default().bold().fg(Color::Green))
+                .title_style(Style::default().bold().fg(theme.focus))
                 .title_alignment(Alignment::Center)
                 .borders(Borders::ALL)
                 .style(Style::default())
-                .border_type(BorderType::Thick)
-                .border_style(Style::default().fg(Color::Green)),
+                .border_type(theme.focused_border)
+                .border_style(Style::default().fg(theme.focus)),
         );
 
         frame.render_widget(Clear, block);
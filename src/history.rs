@@ -1,22 +1,150 @@
 use core::str;
-use std::{fs, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
 
+use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::UnboundedSender;
 
 use ratatui::{
-    layout::{Alignment, Constraint, Direction, Layout},
-    style::{Color, Style, Stylize},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Style, Stylize},
     text::Text,
     widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
     Frame,
 };
 
 use crate::{
-    app::FocusedBlock,
+    app::{AppResult, FocusedBlock},
+    chat::{Chat, Message},
     event::Event,
+    llm::LLMRole,
     notification::{Notification, NotificationLevel},
+    theme::Theme,
 };
 
+const ARCHIVE_PREFIX: &str = "tenere.archive-";
+
+// prefixes of the messages in the plain text archives
+const USER_PREFIX: &str = "👤 : ";
+const ASSISTANT_PREFIX: &str = "🤖: ";
+
+pub fn data_directory() -> PathBuf {
+    dirs::data_dir().unwrap().join("tenere")
+}
+
+// Returns the first archive file name that is not used yet in the directory
+pub fn next_archive_name(directory: &Path) -> String {
+    let index = fs::read_dir(directory)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| archive_index(&entry.ok()?.file_name().to_string_lossy()))
+                .map(|index| index + 1)
+                .max()
+                .unwrap_or(0)
+        })
+        .unwrap_or(0);
+
+    format!("{}{}", ARCHIVE_PREFIX, index)
+}
+
+fn archive_index(file_name: &str) -> Option<usize> {
+    file_name.strip_prefix(ARCHIVE_PREFIX)?.parse().ok()
+}
+
+/// A saved conversation.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Archive {
+    #[serde(skip)]
+    pub file_name: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+
+    // file name of the archive this chat was forked from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+
+    pub messages: Vec<Message>,
+}
+
+impl Archive {
+    pub fn new(messages: Vec<Message>) -> Self {
+        Self {
+            messages,
+            ..Default::default()
+        }
+    }
+
+    /// Parse the content of an archive file.
+    /// Archives written by older versions are plain text and are parsed using the role prefixes.
+    pub fn parse(text: &str) -> Self {
+        if let Ok(archive) = serde_json::from_str::<Archive>(text) {
+            return archive;
+        }
+
+        // a message starts with a line starting with its prefix, the prefixes elsewhere are text
+        let mut messages: Vec<Message> = Vec::new();
+        for line in text.lines() {
+            if let Some(content) = line.strip_prefix(USER_PREFIX) {
+                messages.push(Message::new(LLMRole::USER, content.to_string()));
+            } else if let Some(content) = line.strip_prefix(ASSISTANT_PREFIX) {
+                messages.push(Message::new(LLMRole::ASSISTANT, content.to_string()));
+            } else if let Some(message) = messages.last_mut() {
+                message.content.push('\n');
+                message.content.push_str(line);
+            }
+        }
+
+        messages
+            .iter_mut()
+            .for_each(|message| message.content = message.content.trim().to_string());
+
+        Self::new(messages)
+    }
+
+    pub fn plain_text(&self) -> String {
+        self.messages
+            .iter()
+            .filter_map(|message| match message.role {
+                LLMRole::USER => Some(format!("{}{}\n", USER_PREFIX, message.content)),
+                LLMRole::ASSISTANT => Some(format!("{}{}\n", ASSISTANT_PREFIX, message.content)),
+                LLMRole::SYSTEM => None,
+            })
+            .collect()
+    }
+
+    /// The messages as markdown, for `/export`
+    pub fn markdown(&self) -> String {
+        self.messages
+            .iter()
+            .filter_map(|message| match message.role {
+                LLMRole::USER => Some(format!("## 👤 User\n\n{}\n", message.content)),
+                LLMRole::ASSISTANT => Some(format!("## 🤖 Assistant\n\n{}\n", message.content)),
+                LLMRole::SYSTEM => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn title(&self) -> String {
+        match &self.title {
+            Some(title) => title.clone(),
+            None => self.plain_text().lines().next().unwrap_or("").to_string(),
+        }
+    }
+
+    pub fn write(&self, directory: &Path) -> AppResult<()> {
+        fs::write(
+            directory.join(&self.file_name),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(())
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct Preview<'a> {
     pub text: Vec<Text<'a>>,
@@ -26,38 +154,59 @@ pub struct Preview<'a> {
 #[derive(Debug, Default, Clone)]
 pub struct History<'a> {
     state: ListState,
-    pub text: Vec<Vec<String>>,
+    pub archives: Vec<Archive>,
+    // rows of the list: the index of an archive in the order of the branches tree,
+    // and its label with the prefix drawing its branch
+    tree: Vec<(usize, String)>,
     pub preview: Preview<'a>,
+    // areas of the last render, to find the block under the mouse
+    pub list_area: Rect,
+    pub preview_area: Rect,
 }
 
-impl History<'_> {
+impl<'a> History<'a> {
     pub fn new() -> Self {
         Self {
             state: ListState::default(),
-            text: Vec::new(),
+            archives: Vec::new(),
+            tree: Vec::new(),
             preview: Preview::default(),
+            list_area: Rect::default(),
+            preview_area: Rect::default(),
+        }
+    }
+
+    // select the archive displayed on the row of the screen
+    pub fn click(&mut self, row: u16) {
+        // below the top border of the list
+        let Some(row) = row.checked_sub(self.list_area.y + 1) else {
+            return;
+        };
+        let index = self.state.offset() + row as usize;
+        if index < self.tree.len() {
+            self.state.select(Some(index));
         }
     }
 
     pub fn move_to_bottom(&mut self) {
-        if !self.text.is_empty() {
-            self.state.select(Some(self.text.len() - 1));
+        if !self.archives.is_empty() {
+            self.state.select(Some(self.archives.len() - 1));
         }
     }
 
     pub fn move_to_top(&mut self) {
-        if !self.text.is_empty() {
+        if !self.archives.is_empty() {
             self.state.select(Some(0));
         }
     }
 
     pub fn scroll_down(&mut self) {
-        if self.text.is_empty() {
+        if self.archives.is_empty() {
             return;
         }
         let i = match self.state.selected() {
             Some(i) => {
-                if i < self.text.len() - 1 {
+                if i < self.archives.len() - 1 {
                     i + 1
                 } else {
                     i
@@ -68,7 +217,7 @@ impl History<'_> {
         self.state.select(Some(i));
     }
     pub fn scroll_up(&mut self) {
-        if self.text.is_empty() {
+        if self.archives.is_empty() {
             return;
         }
         let i = match self.state.selected() {
@@ -92,17 +241,85 @@ impl History<'_> {
         }
     }
 
+    pub fn selected(&self) -> Option<&Archive> {
+        self.state
+            .selected()
+            .and_then(|row| self.tree.get(row))
+            .map(|(index, _)| &self.archives[*index])
+    }
+
+    // Build the branches tree again, after the archives changed.
+    // The archives whose parent is missing, or in a cycle, are at the root.
+    fn update_tree(&mut self) {
+        let positions: HashMap<&str, usize> = self
+            .archives
+            .iter()
+            .enumerate()
+            .map(|(index, archive)| (archive.file_name.as_str(), index))
+            .collect();
+
+        let parents: Vec<Option<usize>> = self
+            .archives
+            .iter()
+            .enumerate()
+            .map(|(index, archive)| {
+                archive
+                    .parent
+                    .as_deref()
+                    .and_then(|parent| positions.get(parent).copied())
+                    .filter(|parent| *parent != index)
+            })
+            .collect();
+
+        let mut tree = Tree {
+            archives: &self.archives,
+            children: vec![Vec::new(); self.archives.len()],
+            visited: vec![false; self.archives.len()],
+            rows: Vec::new(),
+        };
+        for (index, parent) in parents.iter().enumerate() {
+            if let Some(parent) = parent {
+                tree.children[*parent].push(index);
+            }
+        }
+
+        for root in (0..self.archives.len()).filter(|index| parents[*index].is_none()) {
+            tree.walk(root, None, "", None);
+        }
+
+        // the archives of a cycle are not reached from a root
+        for index in 0..self.archives.len() {
+            if !tree.visited[index] {
+                tree.walk(index, None, "", None);
+            }
+        }
+
+        self.tree = tree.rows;
+    }
+
     // load chat in the history from data directory
     pub fn load_history(&mut self, sender: UnboundedSender<Event>) {
-        let directory_path: PathBuf = dirs::data_dir().unwrap().join("tenere");
+        let directory_path: PathBuf = data_directory();
 
         if let Ok(paths) = fs::read_dir(directory_path.clone()) {
+            let mut archives: Vec<(usize, PathBuf)> = paths
+                .filter_map(|path| {
+                    let path = path.ok()?;
+                    if !path.file_type().ok()?.is_file() {
+                        return None;
+                    }
+                    let index = archive_index(&path.file_name().to_string_lossy())?;
+                    Some((index, path.path()))
+                })
+                .collect();
+
+            archives.sort();
+
             // foreach archive file we add it to history
-            for path in paths {
-                if path.as_ref().unwrap().file_type().unwrap().is_file() {
-                    self.load_chat_from_file(path.unwrap().path().to_str().unwrap());
-                }
+            for (_, path) in archives {
+                self.load_chat_from_file(path.to_str().unwrap());
             }
+            self.update_tree();
 
             let notif = Notification::new("History loaded".to_string(), NotificationLevel::Info);
 
@@ -110,25 +327,74 @@ impl History<'_> {
         }
     }
 
-    /// Add to history the archive file if exists
-    pub fn load_chat_from_file(&mut self, archive_file_name: &str) {
+    // Add to history the archive file if exists
+    fn load_chat_from_file(&mut self, archive_file_name: &str) {
         if let Ok(text) = std::fs::read_to_string(archive_file_name) {
+            let mut archive = Archive::parse(&text);
+            archive.file_name = Path::new(archive_file_name)
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .to_string();
+
             // push full conversation in preview
-            self.preview.text.push(Text::from(text.clone()));
-            // get first line of the conversation
-            let first_line: String = text.lines().next().unwrap_or("").to_string();
-            self.text.push(vec![first_line]);
+            self.preview.text.push(Text::from(archive.plain_text()));
+            self.archives.push(archive);
         }
     }
 
-    // call after adding new chat in history (Starting a new chat)
-    // with the index of the chat in history to save
-    pub fn save(&mut self, chat_index_in_history: usize, sender: UnboundedSender<Event>) {
-        let file_name = format!("tenere.archive-{}", chat_index_in_history);
-        let file_path: PathBuf = dirs::data_dir().unwrap().join("tenere").join(file_name);
+    /// Add the chat to the history and save it.
+    /// A chat resumed from the history replaces its archive instead of creating a new one.
+    /// Returns the file name of the archive.
+    pub fn archive_chat(
+        &mut self,
+        chat: &Chat<'a>,
+        sender: UnboundedSender<Event>,
+    ) -> Option<String> {
+        if chat.plain_chat.is_empty() {
+            return None;
+        }
 
-        if !self.text.is_empty() {
-            match std::fs::write(file_path.clone(), self.text[chat_index_in_history].join("")) {
+        let index = match chat
+            .archive
+            .as_ref()
+            .and_then(|name| self.archives.iter().position(|a| &a.file_name == name))
+        {
+            Some(index) => {
+                self.archives[index].messages = chat.messages.clone();
+                self.preview.text[index] = chat.formatted_chat.clone();
+                index
+            }
+            None => {
+                let mut archive = Archive::new(chat.messages.clone());
+                archive.file_name = next_archive_name(&data_directory());
+                archive.parent = chat.parent.clone();
+                self.archives.push(archive);
+                self.preview.text.push(chat.formatted_chat.clone());
+                self.archives.len() - 1
+            }
+        };
+
+        self.update_tree();
+        self.save(index, sender);
+
+        Some(self.archives[index].file_name.clone())
+    }
+
+    /// Whether the chat is empty or its archive in the history has all its messages
+    pub fn is_saved(&self, chat: &Chat<'a>) -> bool {
+        chat.messages.is_empty()
+            || chat.archive.as_ref().is_some_and(|name| {
+                self.archives
+                    .iter()
+                    .any(|archive| &archive.file_name == name && archive.messages == chat.messages)
+            })
+    }
+
+    // save the chat at the given index of the history in its archive file
+    pub fn save(&mut self, chat_index_in_history: usize, sender: UnboundedSender<Event>) {
+        if let Some(archive) = self.archives.get(chat_index_in_history) {
+            match archive.write(&data_directory()) {
                 Ok(_) => {
                     let notif =
                         Notification::new("Chat saved".to_string(), NotificationLevel::Info);
@@ -144,7 +410,7 @@ impl History<'_> {
         }
     }
 
-    pub fn render(&mut self, frame: &mut Frame, focused_block: &FocusedBlock) {
+    pub fn render(&mut self, frame: &mut Frame, focused_block: &FocusedBlock, theme: &Theme) {
         let layout = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
@@ -163,7 +429,7 @@ impl History<'_> {
             ])
             .split(layout)[1];
 
-        if !self.text.is_empty() && self.state.selected().is_none() {
+        if !self.archives.is_empty() && self.state.selected().is_none() {
             *self.state.offset_mut() = 0;
             self.state.select(Some(0));
         }
@@ -176,13 +442,13 @@ impl History<'_> {
             (chunks[0], chunks[1])
         };
 
+        self.list_area = history_block;
+        self.preview_area = preview_block;
+
         let items = self
-            .text
+            .tree
             .iter()
-            .map(|chat| match chat.first() {
-                Some(v) => ListItem::new(v.to_owned()),
-                None => ListItem::new(""),
-            })
+            .map(|(_, label)| ListItem::new(label.as_str()))
             .collect::<Vec<ListItem>>();
 
         let list = List::new(items)
@@ -196,19 +462,24 @@ impl History<'_> {
                     })
                     .title_alignment(Alignment::Center)
                     .style(Style::default())
+                    .border_type(match focused_block {
+                        FocusedBlock::History => theme.focused_border,
+                        _ => theme.border,
+                    })
                     .border_style(match focused_block {
-                        FocusedBlock::History => Style::default().fg(Color::Green),
+                        FocusedBlock::History => Style::default().fg(theme.focus),
                         _ => Style::default(),
                     }),
             )
-            .highlight_style(Style::default().bg(Color::DarkGray));
+            .highlight_style(Style::default().bg(theme.selection));
 
-        let preview = Paragraph::new(match self.state.selected() {
-            Some(i) => self.preview.text[i].clone(),
-            None => Text::raw(""),
-        })
+        let preview = Paragraph::new(
+            match self.state.selected().and_then(|row| self.tree.get(row)) {
+                Some((i, _)) => self.preview.text[*i].clone(),
+                None => Text::raw(""),
+            },
+        )
         .wrap(Wrap { trim: false })
-        .scroll((self.preview.scroll as u16, 0))
         .block(
             Block::default()
                 .title(" Preview ")
@@ -219,14 +490,196 @@ impl History<'_> {
                 .title_alignment(Alignment::Center)
                 .borders(Borders::ALL)
                 .style(Style::default())
+                .border_type(match focused_block {
+                    FocusedBlock::Preview => theme.focused_border,
+                    _ => theme.border,
+                })
                 .border_style(match focused_block {
-                    FocusedBlock::Preview => Style::default().fg(Color::Green),
+                    FocusedBlock::Preview => Style::default().fg(theme.focus),
                     _ => Style::default(),
                 }),
         );
 
+        // do not scroll past the end of the chat
+        let max_scroll = preview
+            .line_count(preview_block.width.saturating_sub(2))
+            .saturating_sub(preview_block.height.saturating_sub(2).into());
+        self.preview.scroll = self.preview.scroll.min(max_scroll);
+        let preview = preview.scroll((self.preview.scroll.try_into().unwrap_or(u16::MAX), 0));
+
         frame.render_widget(Clear, block);
         frame.render_widget(preview, preview_block);
         frame.render_stateful_widget(list, history_block, &mut self.state);
     }
 }
+
+// Walk of the branches tree of the history, from its roots
+struct Tree<'a> {
+    archives: &'a [Archive],
+    children: Vec<Vec<usize>>,
+    visited: Vec<bool>,
+    rows: Vec<(usize, String)>,
+}
+
+impl Tree<'_> {
+    fn walk(
+        &mut self,
+        index: usize,
+        parent: Option<usize>,
+        prefix: &str,
+        is_last_child: Option<bool>,
+    ) {
+        self.visited[index] = true;
+
+        let (branch, children_prefix) = match is_last_child {
+            None => ("", prefix.to_string()),
+            Some(true) => ("└─ ", format!("{}   ", prefix)),
+            Some(false) => ("├─ ", format!("{}│  ", prefix)),
+        };
+        self.rows.push((
+            index,
+            format!("{}{}{}", prefix, branch, self.label(index, parent)),
+        ));
+
+        let children: Vec<usize> = self.children[index]
+            .iter()
+            .copied()
+            .filter(|child| !self.visited[*child])
+            .collect();
+
+        for (i, child) in children.iter().enumerate() {
+            self.walk(
+                *child,
+                Some(index),
+                &children_prefix,
+                Some(i == children.len() - 1),
+            );
+        }
+    }
+
+    // A branch is labeled with its first message that differs from its parent
+    fn label(&self, index: usize, parent: Option<usize>) -> String {
+        let archive = &self.archives[index];
+
+        parent
+            .and_then(|parent| {
+                let parent_messages = &self.archives[parent].messages;
+                archive
+                    .messages
+                    .iter()
+                    .enumerate()
+                    .find(|(i, message)| parent_messages.get(*i) != Some(*message))
+                    .map(|(_, message)| Archive::new(vec![message.clone()]).title())
+            })
+            .unwrap_or_else(|| archive.title())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contents(archive: &Archive) -> Vec<(LLMRole, &str)> {
+        archive
+            .messages
+            .iter()
+            .map(|message| (message.role.clone(), message.content.as_str()))
+            .collect()
+    }
+
+    fn archive(file_name: &str, parent: Option<&str>, prompts: &[&str]) -> Archive {
+        let mut archive = Archive::new(
+            prompts
+                .iter()
+                .map(|prompt| Message::new(LLMRole::USER, prompt.to_string()))
+                .collect(),
+        );
+        archive.file_name = file_name.to_string();
+        archive.parent = parent.map(str::to_string);
+        archive
+    }
+
+    fn rows(archives: Vec<Archive>) -> Vec<String> {
+        let mut history = History::new();
+        history.archives = archives;
+        history.update_tree();
+        history.tree.into_iter().map(|(_, label)| label).collect()
+    }
+
+    #[test]
+    fn tree_of_branches() {
+        let rows = rows(vec![
+            archive("a", None, &["one"]),
+            archive("b", Some("a"), &["one", "two"]),
+            archive("c", Some("a"), &["one", "three"]),
+            archive("d", Some("b"), &["one", "two", "four"]),
+            archive("e", Some("missing"), &["five"]),
+        ]);
+
+        assert_eq!(
+            rows,
+            [
+                "👤 : one",
+                "├─ 👤 : two",
+                "│  └─ 👤 : four",
+                "└─ 👤 : three",
+                "👤 : five"
+            ]
+        );
+    }
+
+    #[test]
+    fn tree_with_a_cycle() {
+        let rows = rows(vec![
+            archive("a", Some("b"), &["one"]),
+            archive("b", Some("a"), &["one", "two"]),
+            archive("c", Some("c"), &["three"]),
+        ]);
+
+        assert_eq!(rows, ["👤 : three", "👤 : one", "└─ 👤 : two"]);
+    }
+
+    #[test]
+    fn parse_plain_text() {
+        let archive = Archive::parse("👤 : hello\n🤖: hi\nthere\n👤 : bye\n🤖: bye\n");
+        assert_eq!(
+            contents(&archive),
+            [
+                (LLMRole::USER, "hello"),
+                (LLMRole::ASSISTANT, "hi\nthere"),
+                (LLMRole::USER, "bye"),
+                (LLMRole::ASSISTANT, "bye"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_prefix_in_an_answer() {
+        let archive = Archive::parse(
+            "👤 : hello\n🤖: the prompts start with 👤 : like\nthis👤 : one\n👤 : bye\n",
+        );
+        assert_eq!(
+            contents(&archive),
+            [
+                (LLMRole::USER, "hello"),
+                (
+                    LLMRole::ASSISTANT,
+                    "the prompts start with 👤 : like\nthis👤 : one"
+                ),
+                (LLMRole::USER, "bye"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_prefix_in_a_prompt() {
+        let archive = Archive::parse("👤 : the prefix is `👤 : `\nin a line 👤 : too\n🤖: ok\n");
+        assert_eq!(
+            contents(&archive),
+            [
+                (LLMRole::USER, "the prefix is `👤 : `\nin a line 👤 : too"),
+                (LLMRole::ASSISTANT, "ok"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use std::{fs, path::Path};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    app::AppResult,
+    chat::Message,
+    history::{data_directory, next_archive_name, Archive},
+    llm::LLMRole,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportFormat {
+    // `conversations.json` from the ChatGPT data export
+    OpenAI,
+    // one `{"role": "...", "content": "..."}` message per line
+    Jsonl,
+}
+
+impl ImportFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "openai" => Some(Self::OpenAI),
+            "jsonl" => Some(Self::Jsonl),
+            _ => None,
+        }
+    }
+
+    fn detect(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("jsonl") => Self::Jsonl,
+            _ => Self::OpenAI,
+        }
+    }
+}
+
+/// Import the conversations of the file into the history.
+/// Returns the number of imported conversations.
+pub fn import(path: &Path, format: Option<ImportFormat>) -> AppResult<usize> {
+    let content = fs::read_to_string(path)?;
+
+    let archives = match format.unwrap_or_else(|| ImportFormat::detect(path)) {
+        ImportFormat::OpenAI => parse_openai(&content)?,
+        ImportFormat::Jsonl => {
+            let mut archive = parse_jsonl(&content)?;
+            archive.title = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string());
+            vec![archive]
+        }
+    };
+
+    let directory = data_directory();
+    fs::create_dir_all(&directory)?;
+
+    let mut imported = 0;
+    for mut archive in archives {
+        if archive.messages.is_empty() {
+            continue;
+        }
+        archive.file_name = next_archive_name(&directory);
+        archive.write(&directory)?;
+        imported += 1;
+    }
+
+    Ok(imported)
+}
+
+fn parse_role(role: &str) -> Option<LLMRole> {
+    match role {
+        "user" => Some(LLMRole::USER),
+        "assistant" => Some(LLMRole::ASSISTANT),
+        "system" => Some(LLMRole::SYSTEM),
+        _ => None,
+    }
+}
+
+fn parse_openai(content: &str) -> AppResult<Vec<Archive>> {
+    let conversations: Vec<Value> = serde_json::from_str(content)?;
+
+    Ok(conversations
+        .iter()
+        .map(|conversation| {
+            let mut archive = Archive::new(openai_messages(conversation));
+            archive.title = conversation["title"].as_str().map(String::from);
+            archive
+        })
+        .collect())
+}
+
+// The export stores every edit and regeneration as a tree of nodes.
+// The displayed conversation is the path from `current_node` up to the root.
+fn openai_messages(conversation: &Value) -> Vec<Message> {
+    let mapping = &conversation["mapping"];
+
+    let mut messages: Vec<Message> = Vec::new();
+    let mut node_id = conversation["current_node"].as_str();
+
+    while let Some(id) = node_id {
+        let node = &mapping[id];
+        let message = &node["message"];
+
+        let role = message["author"]["role"].as_str().and_then(parse_role);
+        let content = message["content"]["parts"]
+            .as_array()
+            .map(|parts| {
+                parts
+                    .iter()
+                    .filter_map(|part| part.as_str())
+                    .collect::<Vec<&str>>()
+                    .join("\n")
+            })
+            .unwrap_or_default();
+
+        if let Some(role) = role {
+            if !content.trim().is_empty() {
+                messages.push(Message::new(role, content));
+            }
+        }
+
+        node_id = node["parent"].as_str();
+    }
+
+    messages.reverse();
+    messages
+}
+
+#[derive(Deserialize)]
+struct JsonlMessage {
+    role: String,
+    content: String,
+}
+
+fn parse_jsonl(content: &str) -> AppResult<Archive> {
+    let mut messages: Vec<Message> = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let message: JsonlMessage = serde_json::from_str(line)
+            .map_err(|e| std::io::Error::other(format!("line {}: {}", index + 1, e)))?;
+
+        let role = parse_role(&message.role).ok_or_else(|| {
+            std::io::Error::other(format!(
+                "line {}: unknown role `{}`",
+                index + 1,
+                message.role
+            ))
+        })?;
+
+        messages.push(Message::new(role, message.content));
+    }
+
+    Ok(Archive::new(messages))
+}
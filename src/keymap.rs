@@ -0,0 +1,486 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+use crate::config::KeyBindings;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, EnumIter)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Quit,
+    StopStream,
+    NewChat,
+    ShowHelp,
+    ShowHistory,
+    SwitchFocus,
+    Dismiss,
+    ScrollDown,
+    ScrollUp,
+    GoToTop,
+    GoToBottom,
+    ResumeChat,
+    SubmitPrompt,
+    NormalMode,
+    OpenEditor,
+    PreviousPrompt,
+    NextPrompt,
+    SearchPrompts,
+    Complete,
+    Compare,
+    NewTab,
+    CloseTab,
+    NextTab,
+    PreviousTab,
+    SelectPreviousMessage,
+    SelectNextMessage,
+    EditMessage,
+    CopyMessage,
+    CopyCodeBlocks,
+    #[serde(rename = "copy_code_block_1")]
+    CopyCodeBlock1,
+    #[serde(rename = "copy_code_block_2")]
+    CopyCodeBlock2,
+    #[serde(rename = "copy_code_block_3")]
+    CopyCodeBlock3,
+    #[serde(rename = "copy_code_block_4")]
+    CopyCodeBlock4,
+    #[serde(rename = "copy_code_block_5")]
+    CopyCodeBlock5,
+    #[serde(rename = "copy_code_block_6")]
+    CopyCodeBlock6,
+    #[serde(rename = "copy_code_block_7")]
+    CopyCodeBlock7,
+    #[serde(rename = "copy_code_block_8")]
+    CopyCodeBlock8,
+    #[serde(rename = "copy_code_block_9")]
+    CopyCodeBlock9,
+    ForkChat,
+    Regenerate,
+    PreviousResponse,
+    NextResponse,
+    Search,
+    NextMatch,
+    PreviousMatch,
+    SelectPreviousAnswer,
+    SelectNextAnswer,
+    KeepAnswer,
+    Confirm,
+    Cancel,
+}
+
+impl Action {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::StopStream => "Stop the stream response",
+            Action::NewChat => "Start new chat and save the previous one to the history",
+            Action::ShowHelp => "Show help",
+            Action::ShowHistory => "Show history",
+            Action::SwitchFocus => "Switch the focus",
+            Action::Dismiss => "Dismiss pop-up / Clear the selection or the search",
+            Action::ScrollDown => "Scroll down",
+            Action::ScrollUp => "Scroll up",
+            Action::GoToTop => "Go to the top",
+            Action::GoToBottom => "Go to the end",
+            Action::ResumeChat => "Resume the selected chat from the history",
+            Action::SubmitPrompt => "Send the prompt",
+            Action::NormalMode => "Switch the prompt to Normal mode",
+            Action::OpenEditor => "Edit the prompt in $VISUAL or $EDITOR",
+            Action::PreviousPrompt => "Recall the previous prompt sent",
+            Action::NextPrompt => "Recall the next prompt sent",
+            Action::SearchPrompts => "Search the prompts sent, in Insert mode",
+            Action::Complete => "Complete the command or the @path typed in the prompt",
+            Action::Compare => "Send the prompt to the models of `compare`",
+            Action::NewTab => "Open a new tab",
+            Action::CloseTab => "Close the tab and save its chat",
+            Action::NextTab => "Go to the next tab",
+            Action::PreviousTab => "Go to the previous tab",
+            Action::SelectPreviousMessage => "Select the previous message in the chat",
+            Action::SelectNextMessage => "Select the next message in the chat",
+            Action::EditMessage => "Edit the selected message and resend it",
+            Action::CopyMessage => "Copy the selected message, or the last answer",
+            Action::CopyCodeBlocks => "Copy the code blocks of the selected message",
+            Action::CopyCodeBlock1
+            | Action::CopyCodeBlock2
+            | Action::CopyCodeBlock3
+            | Action::CopyCodeBlock4
+            | Action::CopyCodeBlock5
+            | Action::CopyCodeBlock6
+            | Action::CopyCodeBlock7
+            | Action::CopyCodeBlock8
+            | Action::CopyCodeBlock9 => "Copy the nth code block of the selected message",
+            Action::ForkChat => "Fork the chat at the selected message",
+            Action::Regenerate => "Regenerate the last answer, or resend a prompt that failed",
+            Action::PreviousResponse => "Switch to the previous response of the answer",
+            Action::NextResponse => "Switch to the next response of the answer",
+            Action::Search => "Search in the chat",
+            Action::NextMatch => "Go to the next match",
+            Action::PreviousMatch => "Go to the previous match",
+            Action::SelectPreviousAnswer => "Select the previous answer of the comparison",
+            Action::SelectNextAnswer => "Select the next answer of the comparison",
+            Action::KeepAnswer => "Keep the selected answer of the comparison",
+            Action::Confirm => "Confirm the action of the pop-up",
+            Action::Cancel => "Cancel the action of the pop-up",
+        }
+    }
+
+    fn default_keys(&self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["q", "ctrl+c"],
+            Action::StopStream => &["ctrl+t"],
+            Action::NewChat => &["ctrl+n"],
+            Action::ShowHelp => &["?"],
+            Action::ShowHistory => &["ctrl+h"],
+            Action::SwitchFocus => &["tab"],
+            Action::Dismiss => &["esc"],
+            Action::ScrollDown => &["j", "down"],
+            Action::ScrollUp => &["k", "up"],
+            Action::GoToTop => &["g g"],
+            Action::GoToBottom => &["G"],
+            Action::ResumeChat => &["enter"],
+            Action::SubmitPrompt => &["enter"],
+            Action::NormalMode => &["esc"],
+            Action::OpenEditor => &["ctrl+e"],
+            Action::PreviousPrompt => &["ctrl+p", "up"],
+            Action::NextPrompt => &["ctrl+n", "down"],
+            Action::SearchPrompts => &["ctrl+r"],
+            Action::Complete => &["tab"],
+            Action::Compare => &["alt+c"],
+            Action::NewTab => &["alt+t"],
+            Action::CloseTab => &["alt+w"],
+            Action::NextTab => &["g t"],
+            Action::PreviousTab => &["g T"],
+            Action::SelectPreviousMessage => &["["],
+            Action::SelectNextMessage => &["]"],
+            Action::EditMessage => &["e"],
+            Action::CopyMessage => &["y"],
+            Action::CopyCodeBlocks => &["Y"],
+            Action::CopyCodeBlock1 => &["1"],
+            Action::CopyCodeBlock2 => &["2"],
+            Action::CopyCodeBlock3 => &["3"],
+            Action::CopyCodeBlock4 => &["4"],
+            Action::CopyCodeBlock5 => &["5"],
+            Action::CopyCodeBlock6 => &["6"],
+            Action::CopyCodeBlock7 => &["7"],
+            Action::CopyCodeBlock8 => &["8"],
+            Action::CopyCodeBlock9 => &["9"],
+            Action::ForkChat => &["f"],
+            Action::Regenerate => &["r"],
+            Action::PreviousResponse => &["<"],
+            Action::NextResponse => &[">"],
+            Action::Search => &["/"],
+            Action::NextMatch => &["n"],
+            Action::PreviousMatch => &["N"],
+            Action::SelectPreviousAnswer => &["h", "left"],
+            Action::SelectNextAnswer => &["l", "right"],
+            Action::KeepAnswer => &["enter"],
+            Action::Confirm => &["y"],
+            Action::Cancel => &["n", "esc"],
+        }
+    }
+
+    /// Number of the code block copied by the action, from 1
+    pub fn code_block(&self) -> Option<usize> {
+        match self {
+            Action::CopyCodeBlock1 => Some(1),
+            Action::CopyCodeBlock2 => Some(2),
+            Action::CopyCodeBlock3 => Some(3),
+            Action::CopyCodeBlock4 => Some(4),
+            Action::CopyCodeBlock5 => Some(5),
+            Action::CopyCodeBlock6 => Some(6),
+            Action::CopyCodeBlock7 => Some(7),
+            Action::CopyCodeBlock8 => Some(8),
+            Action::CopyCodeBlock9 => Some(9),
+            _ => None,
+        }
+    }
+}
+
+/// A key with its modifiers. The shift modifier is kept in the case of the characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl Key {
+    pub fn new(code: KeyCode, mut modifiers: KeyModifiers) -> Self {
+        if let KeyCode::Char(_) | KeyCode::BackTab = code {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Self { code, modifiers }
+    }
+
+    // a character typed in a text, not a shortcut
+    pub fn is_text(&self) -> bool {
+        matches!(self.code, KeyCode::Char(_))
+            && !self
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+    }
+}
+
+impl From<KeyEvent> for Key {
+    fn from(key_event: KeyEvent) -> Self {
+        Self::new(key_event.code, key_event.modifiers)
+    }
+}
+
+impl FromStr for Key {
+    type Err = String;
+
+    // e.g. `q`, `G`, `ctrl+c`, `alt+enter` or `f1`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (modifiers, key) = match s.rsplit_once('+') {
+            // `+` alone, or as the key of a combination
+            Some((modifiers, "")) => (modifiers.strip_suffix('+').unwrap_or(modifiers), "+"),
+            Some((modifiers, key)) => (modifiers, key),
+            None => ("", s),
+        };
+
+        let mut key_modifiers = KeyModifiers::NONE;
+        for modifier in modifiers.split('+').filter(|modifier| !modifier.is_empty()) {
+            key_modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("Unknown modifier `{}` in `{}`", modifier, s)),
+            };
+        }
+
+        let code = match key.to_lowercase().as_str() {
+            "enter" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "space" => KeyCode::Char(' '),
+            name if name.len() > 1 && name.starts_with('f') => match name[1..].parse() {
+                Ok(n) => KeyCode::F(n),
+                Err(_) => return Err(format!("Unknown key `{}` in `{}`", key, s)),
+            },
+            _ => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if key_modifiers.contains(KeyModifiers::SHIFT) => {
+                        KeyCode::Char(c.to_ascii_uppercase())
+                    }
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => return Err(format!("Unknown key `{}` in `{}`", key, s)),
+                }
+            }
+        };
+
+        Ok(Self::new(code, key_modifiers))
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "ctrl + ")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "alt + ")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "shift + ")?;
+        }
+
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            KeyCode::Esc => write!(f, "Esc"),
+            KeyCode::PageUp => write!(f, "PageUp"),
+            KeyCode::PageDown => write!(f, "PageDown"),
+            KeyCode::BackTab => write!(f, "shift + Tab"),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+// A key, or several keys pressed one after the other
+fn parse_sequence(s: &str) -> Result<Vec<Key>, String> {
+    let keys = s
+        .split_whitespace()
+        .map(Key::from_str)
+        .collect::<Result<Vec<Key>, String>>()?;
+
+    match keys.is_empty() {
+        true => Err("Empty key binding".to_string()),
+        false => Ok(keys),
+    }
+}
+
+fn format_sequence(keys: &[Key]) -> String {
+    // `gg` rather than `g g`
+    let separator = match keys.iter().all(Key::is_text) {
+        true => "",
+        false => " ",
+    };
+
+    keys.iter()
+        .map(Key::to_string)
+        .collect::<Vec<String>>()
+        .join(separator)
+}
+
+/// Key bindings of the `[keymap]` section: a key sequence or a list of them
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Bindings {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Bindings {
+    fn sequences(&self) -> Vec<&str> {
+        match self {
+            Bindings::One(sequence) => vec![sequence],
+            Bindings::Many(sequences) => sequences.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+/// The key sequences bound to each action
+#[derive(Debug, Clone, Default)]
+pub struct Keymap {
+    bindings: Vec<(Action, Vec<Vec<Key>>)>,
+}
+
+impl Keymap {
+    /// The default bindings, with the keys of `[key_bindings]`, replaced by the ones of `[keymap]`
+    pub fn new(
+        key_bindings: &KeyBindings,
+        overrides: &HashMap<Action, Bindings>,
+    ) -> Result<Self, String> {
+        let mut bindings = Vec::new();
+
+        for action in Action::iter() {
+            let sequences = match overrides.get(&action) {
+                Some(sequences) => sequences
+                    .sequences()
+                    .into_iter()
+                    .map(parse_sequence)
+                    .collect::<Result<Vec<Vec<Key>>, String>>()?,
+                None => match action {
+                    Action::ShowHelp => vec![vec![Key::new(
+                        KeyCode::Char(key_bindings.show_help),
+                        KeyModifiers::NONE,
+                    )]],
+                    Action::ShowHistory => vec![vec![Key::new(
+                        KeyCode::Char(key_bindings.show_history),
+                        KeyModifiers::CONTROL,
+                    )]],
+                    Action::NewChat => vec![vec![Key::new(
+                        KeyCode::Char(key_bindings.new_chat),
+                        KeyModifiers::CONTROL,
+                    )]],
+                    Action::StopStream => vec![vec![Key::new(
+                        KeyCode::Char(key_bindings.stop_stream),
+                        KeyModifiers::CONTROL,
+                    )]],
+                    _ => action
+                        .default_keys()
+                        .iter()
+                        .map(|sequence| parse_sequence(sequence))
+                        .collect::<Result<Vec<Vec<Key>>, String>>()?,
+                },
+            };
+
+            bindings.push((action, sequences));
+        }
+
+        Ok(Self { bindings })
+    }
+
+    /// Actions bound to the key sequence
+    pub fn actions(&self, keys: &[Key]) -> Vec<Action> {
+        self.bindings
+            .iter()
+            .filter(|(_, sequences)| sequences.iter().any(|sequence| sequence == keys))
+            .map(|(action, _)| *action)
+            .collect()
+    }
+
+    // a sequence starts with the keys
+    fn is_prefix(&self, keys: &[Key]) -> bool {
+        self.bindings.iter().any(|(_, sequences)| {
+            sequences
+                .iter()
+                .any(|sequence| sequence.len() > keys.len() && sequence.starts_with(keys))
+        })
+    }
+
+    /// Keys waiting for the next ones to complete a sequence
+    pub fn pending(&self, keys: &[Key]) -> Vec<Key> {
+        if self.is_prefix(keys) {
+            keys.to_vec()
+        } else if keys.len() > 1 && self.is_prefix(&keys[keys.len() - 1..]) {
+            keys[keys.len() - 1..].to_vec()
+        } else {
+            Vec::new()
+        }
+    }
+
+    pub fn is_bound(&self, action: Action, key: Key) -> bool {
+        self.actions(&[key]).contains(&action)
+    }
+
+    /// The key sequences of the action, e.g. `j or Down`
+    pub fn keys(&self, action: Action) -> String {
+        self.bindings
+            .iter()
+            .find(|(bound, _)| *bound == action)
+            .map(|(_, sequences)| {
+                sequences
+                    .iter()
+                    .map(|sequence| format_sequence(sequence))
+                    .collect::<Vec<String>>()
+                    .join(" or ")
+            })
+            .unwrap_or_default()
+    }
+
+    /// The actions with their keys, in the order of the help.
+    /// The actions copying a code block share a row, e.g. `1, 2, …, 9`.
+    pub fn help(&self) -> Vec<(String, &'static str)> {
+        let mut help = Vec::new();
+        for (action, sequences) in &self.bindings {
+            match action.code_block() {
+                Some(1) => {
+                    let keys = self
+                        .bindings
+                        .iter()
+                        .filter(|(action, _)| action.code_block().is_some())
+                        .map(|(action, _)| self.keys(*action))
+                        .filter(|keys| !keys.is_empty())
+                        .collect::<Vec<String>>();
+                    if !keys.is_empty() {
+                        help.push((keys.join(", "), action.description()));
+                    }
+                }
+                Some(_) => {}
+                None if !sequences.is_empty() => {
+                    help.push((self.keys(*action), action.description()))
+                }
+                None => {}
+            }
+        }
+        help
+    }
+}
@@ -22,6 +22,8 @@ pub mod formatter;
 
 pub mod prompt;
 
+pub mod prompt_history;
+
 pub mod help;
 
 pub mod history;
@@ -31,3 +33,35 @@ pub mod chat;
 pub mod llamacpp;
 
 pub mod ollama;
+
+pub mod import;
+
+pub mod autosave;
+
+pub mod confirmation;
+
+pub mod clipboard;
+
+pub mod markdown;
+
+pub mod search;
+
+pub mod status;
+
+pub mod session;
+
+pub mod comparison;
+
+pub mod theme;
+
+pub mod keymap;
+
+pub mod editor;
+
+pub mod command;
+
+pub mod attachment;
+
+pub mod shell;
+
+pub mod vim;
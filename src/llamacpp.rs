@@ -1,14 +1,15 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::event::Event;
 use async_trait::async_trait;
 use regex::Regex;
 use tokio::sync::mpsc::UnboundedSender;
 
 use crate::config::LLamacppConfig;
-use crate::llm::{LLMAnswer, LLMRole, LLM};
+use crate::llm::{LLMAnswer, LLMRole, Stats, LLM};
 use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
 use serde_json::{json, Value};
 use std;
 use std::collections::HashMap;
@@ -19,6 +20,7 @@ pub struct LLamacpp {
     url: String,
     api_key: Option<String>,
     messages: Vec<HashMap<String, String>>,
+    temperature: Option<f64>,
 }
 
 impl LLamacpp {
@@ -33,6 +35,7 @@ impl LLamacpp {
             url: config.url,
             api_key,
             messages: Vec::new(),
+            temperature: None,
         }
     }
 }
@@ -43,6 +46,14 @@ impl LLM for LLamacpp {
         self.messages = Vec::new();
     }
 
+    fn set_temperature(&mut self, temperature: f64) {
+        self.temperature = Some(temperature);
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.messages.truncate(len);
+    }
+
     fn append_chat_msg(&mut self, msg: String, role: LLMRole) {
         let mut conv: HashMap<String, String> = HashMap::new();
         conv.insert("role".to_string(), role.to_string());
@@ -52,7 +63,7 @@ impl LLM for LLamacpp {
 
     async fn ask(
         &self,
-        sender: UnboundedSender<Event>,
+        sender: UnboundedSender<LLMAnswer>,
         terminate_response_signal: Arc<AtomicBool>,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let mut headers = HeaderMap::new();
@@ -74,22 +85,41 @@ impl LLM for LLamacpp {
 
         messages.extend(self.messages.clone());
 
-        let body: Value = json!({
+        let mut body: Value = json!({
             "messages": messages,
             "stream": true,
+            "stream_options": {"include_usage": true},
         });
 
-        let response = self
+        if let Some(temperature) = self.temperature {
+            body["temperature"] = json!(temperature);
+        }
+
+        let mut response = self
             .client
             .post(&self.url)
-            .headers(headers)
+            .headers(headers.clone())
             .json(&body)
             .send()
             .await?;
 
+        // some compatible servers reject the usage option, ask again without it
+        if response.status() == StatusCode::BAD_REQUEST {
+            if let Some(body) = body.as_object_mut() {
+                body.remove("stream_options");
+            }
+            response = self
+                .client
+                .post(&self.url)
+                .headers(headers)
+                .json(&body)
+                .send()
+                .await?;
+        }
+
         match response.error_for_status() {
             Ok(mut res) => {
-                sender.send(Event::LLMEvent(LLMAnswer::StartAnswer))?;
+                sender.send(LLMAnswer::StartAnswer)?;
                 let re = Regex::new(r"data:\s(.*)")?;
                 while let Some(chunk) = res.chunk().await? {
                     let chunk = std::str::from_utf8(&chunk)?;
@@ -97,12 +127,12 @@ impl LLM for LLamacpp {
                     for captures in re.captures_iter(chunk) {
                         if let Some(data_json) = captures.get(1) {
                             if terminate_response_signal.load(Ordering::Relaxed) {
-                                sender.send(Event::LLMEvent(LLMAnswer::EndAnswer))?;
+                                sender.send(LLMAnswer::EndAnswer)?;
                                 return Ok(());
                             }
 
                             if data_json.as_str() == "[DONE]" {
-                                sender.send(Event::LLMEvent(LLMAnswer::EndAnswer))?;
+                                sender.send(LLMAnswer::EndAnswer)?;
                                 return Ok(());
                             }
                             let answer: Value = serde_json::from_str(data_json.as_str())?;
@@ -110,7 +140,24 @@ impl LLM for LLamacpp {
                             let msg = answer["choices"][0]["delta"]["content"].as_str();
 
                             if let Some(msg) = msg {
-                                sender.send(Event::LLMEvent(LLMAnswer::Answer(msg.to_string())))?;
+                                sender.send(LLMAnswer::Answer(msg.to_string()))?;
+                            }
+
+                            // the last chunk has the token counts, and the server timings
+                            if answer["usage"].is_object() {
+                                let stats = Stats {
+                                    model: Some(match answer["model"].as_str() {
+                                        Some(model) => format!("llamacpp/{}", model),
+                                        None => "llamacpp".to_string(),
+                                    }),
+                                    prompt_tokens: answer["usage"]["prompt_tokens"].as_u64(),
+                                    completion_tokens: answer["usage"]["completion_tokens"]
+                                        .as_u64(),
+                                    generation_time: answer["timings"]["predicted_ms"]
+                                        .as_f64()
+                                        .map(|ms| Duration::from_secs_f64(ms / 1000.0)),
+                                };
+                                sender.send(LLMAnswer::Stats(stats))?;
                             }
                         }
                     }
@@ -119,7 +166,7 @@ impl LLM for LLamacpp {
             Err(e) => return Err(Box::new(e)),
         }
 
-        sender.send(Event::LLMEvent(LLMAnswer::EndAnswer))?;
+        sender.send(LLMAnswer::EndAnswer)?;
 
         Ok(())
     }
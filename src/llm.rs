@@ -1,11 +1,11 @@
 use crate::chatgpt::ChatGPT;
 use crate::config::Config;
-use crate::event::Event;
 use crate::llamacpp::LLamacpp;
 use crate::ollama::Ollama;
 use async_trait::async_trait;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::sync::atomic::AtomicBool;
+use std::time::Duration;
 use strum_macros::Display;
 use strum_macros::EnumIter;
 use tokio::sync::mpsc::UnboundedSender;
@@ -16,23 +16,40 @@ use std::sync::Arc;
 pub trait LLM: Send + Sync {
     async fn ask(
         &self,
-        sender: UnboundedSender<Event>,
+        sender: UnboundedSender<LLMAnswer>,
         terminate_response_signal: Arc<AtomicBool>,
     ) -> Result<(), Box<dyn std::error::Error>>;
 
     fn append_chat_msg(&mut self, msg: String, role: LLMRole);
+    // keep only the first `len` messages of the conversation
+    fn truncate(&mut self, len: usize);
     fn clear(&mut self);
+    fn set_temperature(&mut self, temperature: f64);
 }
 
 #[derive(Clone, Debug)]
 pub enum LLMAnswer {
     StartAnswer,
     Answer(String),
+    Stats(Stats),
     EndAnswer,
+    // the request failed, the error is shown but not added to the conversation
+    Error(String),
 }
 
-#[derive(EnumIter, Display, Debug)]
+/// Model, token counts and timing reported by the backend at the end of an answer
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    pub model: Option<String>,
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    // time spent generating the completion tokens, when the backend reports it
+    pub generation_time: Option<Duration>,
+}
+
+#[derive(EnumIter, Display, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
 pub enum LLMRole {
     ASSISTANT,
     SYSTEM,
@@ -57,4 +74,37 @@ impl LLMModel {
             LLMBackend::Ollama => Box::new(Ollama::new(config.ollama.clone().unwrap())),
         }
     }
+
+    /// Backend of a name like `ollama/llama3`, with the settings of its config section.
+    /// The model is optional and replaces the one of the config section.
+    pub fn from_name(name: &str, config: &Config) -> Result<Box<dyn LLM>, String> {
+        let (backend, model) = match name.split_once('/') {
+            Some((backend, model)) => (backend, Some(model.to_string())),
+            None => (name, None),
+        };
+
+        match backend {
+            "chatgpt" => {
+                let mut chatgpt = config.chatgpt.clone();
+                if let Some(model) = model {
+                    chatgpt.model = model;
+                }
+                Ok(Box::new(ChatGPT::new(chatgpt)))
+            }
+            "llamacpp" => match config.llamacpp.clone() {
+                Some(llamacpp) => Ok(Box::new(LLamacpp::new(llamacpp))),
+                None => Err("Config for LLamacpp is not provided".to_string()),
+            },
+            "ollama" => match config.ollama.clone() {
+                Some(mut ollama) => {
+                    if let Some(model) = model {
+                        ollama.model = model;
+                    }
+                    Ok(Box::new(Ollama::new(ollama)))
+                }
+                None => Err("Config for Ollama is not provided".to_string()),
+            },
+            _ => Err(format!("Unknown backend `{}` in `{}`", backend, name)),
+        }
+    }
 }
@@ -1,18 +1,23 @@
 use ratatui::backend::CrosstermBackend;
 use ratatui::Terminal;
 use std::{env, io, path::PathBuf};
-use tenere::app::{App, AppResult};
+use tenere::app::{App, AppResult, FocusedBlock};
+use tenere::autosave::Autosave;
 use tenere::config::Config;
+use tenere::confirmation::{Confirmation, ConfirmationAction};
+use tenere::editor;
 use tenere::event::{Event, EventHandler};
 use tenere::formatter::Formatter;
-use tenere::handler::handle_key_events;
-use tenere::llm::{LLMAnswer, LLMRole};
+use tenere::handler::{
+    handle_answer_events, handle_compare_events, handle_edited_prompt, handle_key_events,
+    handle_mouse_events, handle_paste, handle_shell_output,
+};
+use tenere::import::{import, ImportFormat};
 use tenere::tui::Tui;
 
 use tenere::llm::LLMModel;
 
 use std::sync::Arc;
-use tokio::sync::Mutex;
 
 use clap::{crate_description, crate_version, Arg, Command};
 
@@ -28,25 +33,76 @@ async fn main() -> AppResult<()> {
                 .help("Path to custom config file")
                 .value_name("FILE"),
         )
+        .subcommand(
+            Command::new("import")
+                .about("Import conversations into the history")
+                .arg(
+                    Arg::new("file")
+                        .help("ChatGPT export (conversations.json) or JSONL file of messages")
+                        .value_name("FILE")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("format")
+                        .short('f')
+                        .long("format")
+                        .help("Format of the file. Detected from the extension by default")
+                        .value_parser(["openai", "jsonl"]),
+                ),
+        )
         .get_matches();
 
+    if let Some(matches) = matches.subcommand_matches("import") {
+        let file = matches
+            .get_one::<String>("file")
+            .map(PathBuf::from)
+            .unwrap();
+        let format = matches
+            .get_one::<String>("format")
+            .and_then(|format| ImportFormat::from_name(format));
+
+        match import(&file, format) {
+            Ok(n) => println!("{} conversations imported", n),
+            Err(e) => {
+                eprintln!("Can not import {}: {}", file.display(), e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
     let config_path = matches.get_one::<String>("config").map(PathBuf::from);
     let config = Arc::new(Config::load(config_path));
 
-    let (formatter_config, formatter_assets) = Formatter::init();
-    let formatter = Formatter::new(&formatter_config, &formatter_assets);
+    let (formatter_config, formatter_assets) = Formatter::init(&config.theme);
 
-    let mut app = App::new(config.clone(), &formatter);
+    if !formatter_assets
+        .themes()
+        .any(|theme| theme == config.theme.syntax_theme)
+    {
+        eprintln!(
+            "Unknown syntax theme `{}`, run `bat --list-themes` to list the themes",
+            config.theme.syntax_theme
+        );
+        std::process::exit(1)
+    }
+
+    let formatter = Formatter::new(
+        &formatter_config,
+        &formatter_assets,
+        config.renderer,
+        &config.theme,
+    );
+
+    let llm = LLMModel::init(&config.llm, config.clone()).await;
 
-    let llm = Arc::new(Mutex::new(
-        LLMModel::init(&config.llm, config.clone()).await,
-    ));
+    let mut app = App::new(config.clone(), &formatter, llm);
 
     let backend = CrosstermBackend::new(io::stdout());
     let terminal = Terminal::new(backend)?;
     let events = EventHandler::new(250);
     let mut tui = Tui::new(terminal, events);
-    tui.init()?;
+    tui.init(app.autosave.clone(), config.mouse)?;
 
     // create data directory if not exists
     app.history
@@ -55,41 +111,66 @@ async fn main() -> AppResult<()> {
     // load potential history data from archive files
     app.history.load_history(tui.events.sender.clone());
 
+    // offer to restore the chats that were not saved in the previous session
+    let archives = Autosave::load();
+    if !archives.is_empty() {
+        let message = match archives.len() {
+            1 => "The chat from the last session was not saved.\nRestore it? Otherwise it is moved to the history.".to_string(),
+            n => format!("The {} chats from the last session were not saved.\nRestore them in tabs? Otherwise they are moved to the history.", n),
+        };
+        app.confirmation = Some(Confirmation::new(
+            message,
+            ConfirmationAction::RestoreChat(archives),
+        ));
+        app.focused_block = FocusedBlock::Confirmation;
+    }
+
     while app.running {
         tui.draw(&mut app)?;
         match tui.events.next().await? {
             Event::Tick => app.tick(),
             Event::Key(key_event) => {
-                handle_key_events(key_event, &mut app, llm.clone(), tui.events.sender.clone())
-                    .await?;
+                let llm = app.llm.clone();
+                handle_key_events(key_event, &mut app, llm, tui.events.sender.clone()).await?;
             }
-            Event::Mouse(_) => {}
-            Event::Resize(_, _) => {}
-            Event::LLMEvent(LLMAnswer::Answer(answer)) => {
-                app.chat
-                    .handle_answer(LLMAnswer::Answer(answer), &formatter);
-            }
-            Event::LLMEvent(LLMAnswer::EndAnswer) => {
-                {
-                    let mut llm = llm.lock().await;
-                    llm.append_chat_msg(app.chat.answer.plain_answer.clone(), LLMRole::ASSISTANT);
-                }
-
-                app.chat.handle_answer(LLMAnswer::EndAnswer, &formatter);
-                app.terminate_response_signal
-                    .store(false, std::sync::atomic::Ordering::Relaxed);
+            Event::Mouse(mouse_event) => {
+                handle_mouse_events(mouse_event, &mut app, tui.events.sender.clone())
             }
-            Event::LLMEvent(LLMAnswer::StartAnswer) => {
-                app.spinner.active = false;
-                app.chat.handle_answer(LLMAnswer::StartAnswer, &formatter);
+            Event::Paste(text) => handle_paste(text, &mut app),
+            Event::Resize(_, _) => {}
+            Event::LLMEvent(session, answer) => {
+                handle_answer_events(session, answer, &mut app).await;
             }
+            Event::CompareEvent(stream, answer) => handle_compare_events(stream, answer, &mut app),
 
             Event::Notification(notification) => {
                 app.notifications.push(notification);
             }
+
+            Event::OpenEditor => {
+                tui.suspend().await?;
+                let edited = editor::edit(&app.prompt.editor.lines().join("\n"));
+                tui.resume(config.mouse)?;
+                handle_edited_prompt(edited, &mut app, tui.events.sender.clone());
+            }
+
+            Event::ShellOutput(outputs) => handle_shell_output(outputs, &mut app),
         }
     }
 
     tui.exit()?;
+
+    // the chats of the other tabs are saved to the history
+    for session in &app.tabs {
+        app.history
+            .archive_chat(&session.chat, tui.events.sender.clone());
+    }
+
+    // only the active chat is kept for the next session
+    if !app.restore_pending() {
+        app.autosave.update(&[&app.chat]);
+        app.autosave.save()?;
+    }
+
     Ok(())
 }
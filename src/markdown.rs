@@ -0,0 +1,680 @@
+use pulldown_cmark::{
+    Alignment, CodeBlockKind, Event, HeadingLevel, LinkType, Options, Parser, Tag, TagEnd,
+};
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
+};
+use std::ops::Range;
+use syntect::{
+    easy::HighlightLines, highlighting::Theme as SyntaxTheme, parsing::SyntaxSet,
+    util::LinesWithEndings,
+};
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::theme::Theme;
+
+const BULLETS: [&str; 3] = ["• ", "◦ ", "▪ "];
+
+/// Renders markdown to styled text, the code blocks are highlighted with syntect
+pub struct Markdown<'a> {
+    syntax_set: &'a SyntaxSet,
+    syntax_theme: &'a SyntaxTheme,
+    theme: &'a Theme,
+    true_color: bool,
+}
+
+impl<'a> Markdown<'a> {
+    pub fn new(syntax_set: &'a SyntaxSet, syntax_theme: &'a SyntaxTheme, theme: &'a Theme) -> Self {
+        let true_color = std::env::var("COLORTERM")
+            .map(|value| value == "truecolor" || value == "24bit")
+            .unwrap_or(false);
+
+        Self {
+            syntax_set,
+            syntax_theme,
+            theme,
+            true_color,
+        }
+    }
+
+    pub fn render(&self, input: &str) -> Text<'static> {
+        let options =
+            Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+
+        let mut writer = Writer::new(self);
+        for event in Parser::new_ext(input, options) {
+            writer.event(event);
+        }
+        writer.flush_line();
+
+        // keep the blank line ending the input, the chat is formatted block by block
+        if writer.blank && input.trim_end_matches([' ', '\t']).ends_with("\n\n") {
+            writer.lines.push(Line::default());
+        }
+
+        Text::from(writer.lines)
+    }
+
+    fn highlight(&self, code: &str, language: &str) -> Vec<Vec<Span<'static>>> {
+        let syntax = self
+            .syntax_set
+            .find_syntax_by_token(language)
+            .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
+        let mut highlighter = HighlightLines::new(syntax, self.syntax_theme);
+
+        LinesWithEndings::from(code)
+            .map(
+                |line| match highlighter.highlight_line(line, self.syntax_set) {
+                    Ok(ranges) => ranges
+                        .into_iter()
+                        .map(|(style, text)| {
+                            Span::styled(
+                                text.trim_end_matches('\n').to_string(),
+                                Style::default().fg(self.color(style.foreground)),
+                            )
+                        })
+                        .collect(),
+                    Err(_) => vec![Span::raw(line.trim_end_matches('\n').to_string())],
+                },
+            )
+            .collect()
+    }
+
+    fn color(&self, color: syntect::highlighting::Color) -> Color {
+        // themes made for the terminal palette store the ansi color in the red channel
+        if color.a == 0 {
+            return Color::Indexed(color.r);
+        }
+
+        if self.true_color {
+            return Color::Rgb(color.r, color.g, color.b);
+        }
+
+        // closest color of the 6x6x6 cube of the 256 colors palette
+        let level = |value: u8| match value {
+            0..48 => 0,
+            48..115 => 1,
+            _ => (value - 35) / 40,
+        };
+        Color::Indexed(16 + 36 * level(color.r) + 6 * level(color.g) + level(color.b))
+    }
+}
+
+/// Byte ranges of the text outside of its code blocks and code spans, one or more per line
+pub fn prose(text: &str) -> Vec<Range<usize>> {
+    let code = Parser::new(text)
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::CodeBlock(_)) | Event::Code(_) => Some(range),
+            _ => None,
+        });
+
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    for code in code.chain(std::iter::once(text.len()..text.len())) {
+        let end = code.start.max(start);
+        let mut line = start;
+        for (index, _) in text[start..end].match_indices('\n') {
+            ranges.push(line..start + index);
+            line = start + index + 1;
+        }
+        ranges.push(line..end);
+        start = code.end.max(start);
+    }
+
+    ranges.retain(|range| !range.is_empty());
+    ranges
+}
+
+enum Container {
+    Quote,
+    // list item, with the width of its marker
+    Item(usize),
+}
+
+struct Table {
+    alignments: Vec<Alignment>,
+    // rows of cells, the first one is the header
+    rows: Vec<Vec<Vec<Span<'static>>>>,
+}
+
+struct Writer<'m, 'a> {
+    markdown: &'m Markdown<'a>,
+    lines: Vec<Line<'static>>,
+    spans: Vec<Span<'static>>,
+    styles: Vec<Style>,
+    containers: Vec<Container>,
+    // next number of the ordered lists, `None` for the bullet lists
+    lists: Vec<Option<u64>>,
+    // marker of the list item waiting for its first line
+    marker: Option<String>,
+    // a blank line goes before the next block
+    blank: bool,
+    // language and content of the code block
+    code: Option<(String, String)>,
+    table: Option<Table>,
+    link: Option<String>,
+}
+
+impl<'m, 'a> Writer<'m, 'a> {
+    fn new(markdown: &'m Markdown<'a>) -> Self {
+        Self {
+            markdown,
+            lines: Vec::new(),
+            spans: Vec::new(),
+            styles: Vec::new(),
+            containers: Vec::new(),
+            lists: Vec::new(),
+            marker: None,
+            blank: false,
+            code: None,
+            table: None,
+            link: None,
+        }
+    }
+
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+
+            Event::Text(text) => match self.code.as_mut() {
+                Some((_, code)) => code.push_str(&text),
+                None => self.push_span(Span::styled(text.to_string(), self.style())),
+            },
+
+            Event::Code(code) => self.push_span(Span::styled(
+                code.to_string(),
+                self.style().fg(self.markdown.theme.inline_code),
+            )),
+
+            Event::Html(html) => {
+                for line in html.lines() {
+                    self.push_span(Span::styled(line.to_string(), self.style()));
+                    self.flush_line();
+                }
+            }
+
+            Event::InlineHtml(html) => self.push_span(Span::styled(html.to_string(), self.style())),
+
+            Event::SoftBreak | Event::HardBreak => self.flush_line(),
+
+            Event::Rule => {
+                self.start_block();
+                self.push_span(Span::styled(
+                    "─".repeat(40),
+                    Style::default().fg(self.markdown.theme.dim),
+                ));
+                self.flush_line();
+                self.blank = true;
+            }
+
+            Event::TaskListMarker(checked) => {
+                self.push_span(Span::styled(
+                    if checked { "[x] " } else { "[ ] " },
+                    Style::default().fg(self.markdown.theme.accent),
+                ));
+            }
+
+            Event::FootnoteReference(name) => {
+                self.push_span(Span::styled(format!("[^{}]", name), self.style()))
+            }
+
+            _ => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph | Tag::HtmlBlock => self.start_block(),
+
+            Tag::Heading { level, .. } => {
+                self.start_block();
+                let style = Style::default()
+                    .fg(self.markdown.theme.heading)
+                    .add_modifier(Modifier::BOLD);
+                self.push_style(match level {
+                    HeadingLevel::H1 => style.add_modifier(Modifier::UNDERLINED),
+                    HeadingLevel::H2 => style,
+                    _ => style
+                        .remove_modifier(Modifier::BOLD)
+                        .fg(self.markdown.theme.subheading),
+                });
+            }
+
+            Tag::BlockQuote(_) => {
+                self.start_block();
+                self.containers.push(Container::Quote);
+                self.push_style(Style::default().add_modifier(Modifier::ITALIC));
+            }
+
+            Tag::CodeBlock(kind) => {
+                self.start_block();
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => info
+                        .split_whitespace()
+                        .next()
+                        .unwrap_or_default()
+                        .to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                self.code = Some((language, String::new()));
+            }
+
+            Tag::List(start) => {
+                if self.lists.is_empty() {
+                    self.start_block();
+                } else {
+                    self.flush_line();
+                }
+                self.lists.push(start);
+            }
+
+            Tag::Item => {
+                self.flush_line();
+                let depth = self.lists.len().saturating_sub(1);
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    _ => BULLETS[depth % BULLETS.len()].to_string(),
+                };
+                self.containers.push(Container::Item(marker.width()));
+                self.marker = Some(marker);
+            }
+
+            Tag::Table(alignments) => {
+                self.start_block();
+                self.table = Some(Table {
+                    alignments,
+                    rows: Vec::new(),
+                });
+            }
+
+            Tag::TableHead | Tag::TableRow => {
+                if let Some(table) = self.table.as_mut() {
+                    table.rows.push(Vec::new());
+                }
+            }
+
+            Tag::TableCell => {
+                if let Some(row) = self.table.as_mut().and_then(|table| table.rows.last_mut()) {
+                    row.push(Vec::new());
+                }
+            }
+
+            Tag::Emphasis => self.push_style(Style::default().add_modifier(Modifier::ITALIC)),
+
+            Tag::Strong => self.push_style(Style::default().add_modifier(Modifier::BOLD)),
+
+            Tag::Strikethrough => {
+                self.push_style(Style::default().add_modifier(Modifier::CROSSED_OUT))
+            }
+
+            Tag::Link {
+                link_type,
+                dest_url,
+                ..
+            }
+            | Tag::Image {
+                link_type,
+                dest_url,
+                ..
+            } => {
+                self.push_style(
+                    Style::default()
+                        .fg(self.markdown.theme.link)
+                        .add_modifier(Modifier::UNDERLINED),
+                );
+                // the text of the autolinks is already the url
+                self.link = match link_type {
+                    LinkType::Autolink | LinkType::Email => None,
+                    _ => Some(dest_url.to_string()),
+                };
+            }
+
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph | TagEnd::HtmlBlock => self.end_block(),
+
+            TagEnd::Heading(_) => {
+                self.styles.pop();
+                self.end_block();
+            }
+
+            TagEnd::BlockQuote(_) => {
+                self.flush_line();
+                self.styles.pop();
+                self.containers.pop();
+                self.blank = true;
+            }
+
+            TagEnd::CodeBlock => {
+                if let Some((language, code)) = self.code.take() {
+                    if !language.is_empty() {
+                        self.push_span(Span::styled(
+                            language.clone(),
+                            Style::default()
+                                .fg(self.markdown.theme.dim)
+                                .add_modifier(Modifier::ITALIC),
+                        ));
+                        self.flush_line();
+                    }
+                    for spans in self.markdown.highlight(&code, &language) {
+                        self.push_line(spans);
+                    }
+                }
+                self.blank = true;
+            }
+
+            TagEnd::List(_) => {
+                self.flush_line();
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.blank = true;
+                }
+            }
+
+            TagEnd::Item => {
+                self.flush_line();
+                self.containers.pop();
+                self.marker = None;
+            }
+
+            TagEnd::Table => {
+                if let Some(table) = self.table.take() {
+                    self.write_table(table);
+                }
+                self.blank = true;
+            }
+
+            TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough => {
+                self.styles.pop();
+            }
+
+            TagEnd::Link | TagEnd::Image => {
+                self.styles.pop();
+                if let Some(url) = self.link.take() {
+                    self.push_span(Span::styled(
+                        format!(" ({})", url),
+                        Style::default().fg(self.markdown.theme.dim),
+                    ));
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    fn style(&self) -> Style {
+        self.styles.last().copied().unwrap_or_default()
+    }
+
+    fn push_style(&mut self, style: Style) {
+        self.styles.push(self.style().patch(style));
+    }
+
+    fn start_block(&mut self) {
+        self.flush_line();
+        if self.blank && !self.lines.is_empty() {
+            self.lines.push(Line::default());
+        }
+        self.blank = false;
+    }
+
+    fn end_block(&mut self) {
+        self.flush_line();
+        self.blank = true;
+    }
+
+    // Indentation of the lists and bars of the quotes the line is in
+    fn prefix(&mut self) -> Vec<Span<'static>> {
+        let last_item = self
+            .containers
+            .iter()
+            .rposition(|container| matches!(container, Container::Item(_)));
+
+        let mut prefix = Vec::new();
+        for (index, container) in self.containers.iter().enumerate() {
+            match container {
+                Container::Quote => prefix.push(Span::styled(
+                    "▎ ",
+                    Style::default().fg(self.markdown.theme.dim),
+                )),
+                Container::Item(width) => match self.marker.take() {
+                    Some(marker) if Some(index) == last_item => prefix.push(Span::styled(
+                        marker,
+                        Style::default().fg(self.markdown.theme.accent),
+                    )),
+                    marker => {
+                        self.marker = marker;
+                        prefix.push(Span::raw(" ".repeat(*width)));
+                    }
+                },
+            }
+        }
+        prefix
+    }
+
+    fn push_span(&mut self, span: Span<'static>) {
+        if let Some(table) = self.table.as_mut() {
+            if let Some(cell) = table.rows.last_mut().and_then(|row| row.last_mut()) {
+                cell.push(span);
+            }
+            return;
+        }
+
+        if self.spans.is_empty() {
+            self.spans = self.prefix();
+        }
+        self.spans.push(span);
+    }
+
+    fn push_line(&mut self, spans: Vec<Span<'static>>) {
+        self.flush_line();
+        let mut line = self.prefix();
+        line.extend(spans);
+        self.lines.push(Line::from(line));
+    }
+
+    fn flush_line(&mut self) {
+        if !self.spans.is_empty() {
+            self.lines.push(Line::from(std::mem::take(&mut self.spans)));
+        }
+    }
+
+    fn write_table(&mut self, table: Table) {
+        let cell_width =
+            |cell: &Vec<Span>| cell.iter().map(|span| span.content.width()).sum::<usize>();
+
+        let columns = table.rows.iter().map(Vec::len).max().unwrap_or(0);
+        let widths: Vec<usize> = (0..columns)
+            .map(|column| {
+                table
+                    .rows
+                    .iter()
+                    .filter_map(|row| row.get(column))
+                    .map(cell_width)
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let separator = || Span::styled(" │ ", Style::default().fg(self.markdown.theme.dim));
+
+        for (index, row) in table.rows.iter().enumerate() {
+            let mut line = Vec::new();
+            for (column, width) in widths.iter().enumerate() {
+                if column > 0 {
+                    line.push(separator());
+                }
+
+                let cell = row.get(column).cloned().unwrap_or_default();
+                let padding = width - cell_width(&cell);
+                let (left, right) = match table.alignments.get(column) {
+                    Some(Alignment::Right) => (padding, 0),
+                    Some(Alignment::Center) => (padding / 2, padding - padding / 2),
+                    _ => (0, padding),
+                };
+
+                line.push(Span::raw(" ".repeat(left)));
+                line.extend(cell.into_iter().map(|span| match index {
+                    0 => span.patch_style(Style::default().add_modifier(Modifier::BOLD)),
+                    _ => span,
+                }));
+                line.push(Span::raw(" ".repeat(right)));
+            }
+            self.push_line(line);
+
+            // under the header
+            if index == 0 {
+                let rule = widths
+                    .iter()
+                    .map(|width| "─".repeat(*width))
+                    .collect::<Vec<String>>()
+                    .join("─┼─");
+                self.push_line(vec![Span::styled(
+                    rule,
+                    Style::default().fg(self.markdown.theme.dim),
+                )]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bat::assets::HighlightingAssets;
+    use ratatui::{backend::TestBackend, widgets::Paragraph, widgets::Wrap, Terminal};
+
+    use super::*;
+
+    fn render(input: &str) -> Text<'static> {
+        let theme = Theme::default();
+        let assets = HighlightingAssets::from_binary();
+        let markdown = Markdown::new(
+            assets.get_syntax_set().unwrap(),
+            assets.get_theme(&theme.syntax_theme),
+            &theme,
+        );
+        markdown.render(input)
+    }
+
+    fn lines(input: &str) -> Vec<String> {
+        render(input)
+            .lines
+            .iter()
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn fenced_code_blocks() {
+        assert_eq!(
+            lines("before\n```rust\nfn main() {\n    println!(\"```\");\n}\n```\nafter\n"),
+            [
+                "before",
+                "",
+                "rust",
+                "fn main() {",
+                "    println!(\"```\");",
+                "}",
+                "",
+                "after",
+            ]
+        );
+
+        // a block without language, and one that is not closed yet
+        assert_eq!(
+            lines("~~~\n```\nnested\n```\n~~~\n\n```\nopen"),
+            ["```", "nested", "```", "", "open"]
+        );
+    }
+
+    #[test]
+    fn nested_lists() {
+        assert_eq!(
+            lines("- one\n  - two\n    - three\n      - four\n- five\n\n1. first\n2. second\n   - inside\n"),
+            [
+                "• one",
+                "  ◦ two",
+                "    ▪ three",
+                "      • four",
+                "• five",
+                "",
+                "1. first",
+                "2. second",
+                "   ◦ inside",
+            ]
+        );
+    }
+
+    #[test]
+    fn tables() {
+        assert_eq!(
+            lines("| a | long header | c |\n|:--|--:|:-:|\n| left | 1 | mid |\n| x | 100 | |\n"),
+            [
+                "a    │ long header │  c ",
+                "─────┼─────────────┼────",
+                "left │           1 │ mid",
+                "x    │         100 │    ",
+            ]
+        );
+    }
+
+    #[test]
+    fn inline_code() {
+        let text = render("run `cargo test` now");
+        let line = &text.lines[0];
+        assert_eq!(line.to_string(), "run cargo test now");
+        assert_eq!(line.spans[1].content, "cargo test");
+        assert_eq!(line.spans[1].style.fg, Some(Theme::default().inline_code));
+        assert_eq!(line.spans[0].style.fg, None);
+
+        // the markers of the code span are kept inside a code block
+        assert_eq!(lines("```\nrun `this`\n```"), ["run `this`"]);
+    }
+
+    #[test]
+    fn wrapped_lines() {
+        // the lines of a paragraph are kept, the long ones are wrapped by the chat
+        let text = render("a first line\na second line that is long\n\n- an item that is long");
+        assert_eq!(text.lines.len(), 4);
+
+        let mut terminal = Terminal::new(TestBackend::new(16, 7)).unwrap();
+        terminal
+            .draw(|frame| {
+                frame.render_widget(
+                    Paragraph::new(text).wrap(Wrap { trim: false }),
+                    frame.area(),
+                )
+            })
+            .unwrap();
+        let rows: Vec<String> = terminal
+            .backend()
+            .buffer()
+            .content
+            .chunks(16)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect())
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                "a first line    ",
+                "a second line   ",
+                "that is long    ",
+                "                ",
+                "• an item that  ",
+                "is long         ",
+                "                ",
+            ]
+        );
+    }
+}
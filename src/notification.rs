@@ -1,11 +1,13 @@
 use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
+    style::{Modifier, Style},
     text::{Line, Text},
-    widgets::{Block, BorderType, Borders, Clear, Paragraph, Wrap},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
     Frame,
 };
 
+use crate::theme::Theme;
+
 #[derive(Debug, Clone)]
 pub struct Notification {
     pub message: String,
@@ -29,11 +31,11 @@ impl Notification {
         }
     }
 
-    pub fn render(&self, index: usize, frame: &mut Frame) {
+    pub fn render(&self, index: usize, frame: &mut Frame, theme: &Theme) {
         let (color, title) = match self.level {
-            NotificationLevel::Info => (Color::Green, "Info"),
-            NotificationLevel::Warning => (Color::Yellow, "Warning"),
-            NotificationLevel::Error => (Color::Red, "Error"),
+            NotificationLevel::Info => (theme.info, "Info"),
+            NotificationLevel::Warning => (theme.warning, "Warning"),
+            NotificationLevel::Error => (theme.error, "Error"),
         };
 
         let mut text = Text::from(vec![
@@ -52,7 +54,7 @@ impl Notification {
                 Block::default()
                     .borders(Borders::ALL)
                     .style(Style::default())
-                    .border_type(BorderType::Thick)
+                    .border_type(theme.focused_border)
                     .border_style(Style::default().fg(color)),
             );
 
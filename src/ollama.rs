@@ -1,13 +1,13 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::config::OllamaConfig;
-use crate::event::Event;
 use async_trait::async_trait;
 use tokio::sync::mpsc::UnboundedSender;
 
-use crate::llm::{LLMAnswer, LLMRole, LLM};
+use crate::llm::{LLMAnswer, LLMRole, Stats, LLM};
 use reqwest::header::HeaderMap;
 use serde_json::{json, Value};
 use std;
@@ -19,6 +19,7 @@ pub struct Ollama {
     url: String,
     model: String,
     messages: Vec<HashMap<String, String>>,
+    temperature: Option<f64>,
 }
 
 impl Ollama {
@@ -28,6 +29,7 @@ impl Ollama {
             url: config.url,
             model: config.model,
             messages: Vec::new(),
+            temperature: None,
         }
     }
 }
@@ -38,6 +40,14 @@ impl LLM for Ollama {
         self.messages = Vec::new();
     }
 
+    fn set_temperature(&mut self, temperature: f64) {
+        self.temperature = Some(temperature);
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.messages.truncate(len);
+    }
+
     fn append_chat_msg(&mut self, msg: String, role: LLMRole) {
         let mut conv: HashMap<String, String> = HashMap::new();
         conv.insert("role".to_string(), role.to_string());
@@ -47,7 +57,7 @@ impl LLM for Ollama {
 
     async fn ask(
         &self,
-        sender: UnboundedSender<Event>,
+        sender: UnboundedSender<LLMAnswer>,
         terminate_response_signal: Arc<AtomicBool>,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let mut headers = HeaderMap::new();
@@ -65,12 +75,16 @@ impl LLM for Ollama {
 
         messages.extend(self.messages.clone());
 
-        let body: Value = json!({
+        let mut body: Value = json!({
             "messages": messages,
             "model": self.model,
             "stream": true,
         });
 
+        if let Some(temperature) = self.temperature {
+            body["options"] = json!({ "temperature": temperature });
+        }
+
         let response = self
             .client
             .post(&self.url)
@@ -81,29 +95,41 @@ impl LLM for Ollama {
 
         match response.error_for_status() {
             Ok(mut res) => {
-                sender.send(Event::LLMEvent(LLMAnswer::StartAnswer))?;
+                sender.send(LLMAnswer::StartAnswer)?;
                 while let Some(chunk) = res.chunk().await? {
                     if terminate_response_signal.load(Ordering::Relaxed) {
-                        sender.send(Event::LLMEvent(LLMAnswer::EndAnswer))?;
+                        sender.send(LLMAnswer::EndAnswer)?;
                         return Ok(());
                     }
 
                     let answer: Value = serde_json::from_slice(chunk.as_ref())?;
 
                     if answer["done"].as_bool().unwrap() {
-                        sender.send(Event::LLMEvent(LLMAnswer::EndAnswer))?;
+                        let stats = Stats {
+                            model: Some(format!(
+                                "ollama/{}",
+                                answer["model"].as_str().unwrap_or(&self.model)
+                            )),
+                            prompt_tokens: answer["prompt_eval_count"].as_u64(),
+                            completion_tokens: answer["eval_count"].as_u64(),
+                            generation_time: answer["eval_duration"]
+                                .as_u64()
+                                .map(Duration::from_nanos),
+                        };
+                        sender.send(LLMAnswer::Stats(stats))?;
+                        sender.send(LLMAnswer::EndAnswer)?;
                         return Ok(());
                     }
 
                     let msg = answer["message"]["content"].as_str().unwrap_or("\n");
 
-                    sender.send(Event::LLMEvent(LLMAnswer::Answer(msg.to_string())))?;
+                    sender.send(LLMAnswer::Answer(msg.to_string()))?;
                 }
             }
             Err(e) => return Err(Box::new(e)),
         }
 
-        sender.send(Event::LLMEvent(LLMAnswer::EndAnswer))?;
+        sender.send(LLMAnswer::EndAnswer)?;
 
         Ok(())
     }
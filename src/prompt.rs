@@ -1,15 +1,20 @@
-use arboard::Clipboard;
 use ratatui::{
     layout::{Margin, Rect},
-    style::{Color, Style},
-    text::Text,
-    widgets::{Block, BorderType, Borders},
+    style::Style,
+    text::{Line, Text},
+    widgets::{Block, Borders},
     Frame,
 };
 use tui_textarea::{CursorMove, TextArea};
 use unicode_width::UnicodeWidthStr;
 
 use crate::app::FocusedBlock;
+use crate::clipboard::Clipboard;
+use crate::notification::NotificationLevel;
+use crate::prompt_history::PromptHistory;
+use crate::theme::Theme;
+use crate::vim::Vim;
+use crate::{attachment, command};
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
 #[derive(Debug, PartialEq)]
@@ -17,12 +22,23 @@ pub enum Mode {
     Normal,
     Insert,
     Visual,
+    // `V`, the selection is made of whole lines
+    VisualLine,
+    // `R`, the typed text replaces the one under the cursor
+    Replace,
 }
 
 pub struct Prompt<'a> {
     pub mode: Mode,
     pub formatted_prompt: Text<'a>,
     pub editor: TextArea<'a>,
+    // index in the chat of the message being edited
+    pub editing: Option<usize>,
+    // the prompts submitted in all the sessions
+    pub history: PromptHistory,
+    // error of a command, or the candidates of its completion, shown until the next key
+    pub notice: Option<(NotificationLevel, String)>,
+    vim: Vim,
 }
 
 impl Default for Prompt<'_> {
@@ -30,247 +46,277 @@ impl Default for Prompt<'_> {
         let mut editor = TextArea::default();
         editor.remove_line_number();
         editor.set_cursor_line_style(Style::default());
-        editor.set_selection_style(Style::default().bg(Color::DarkGray));
 
         Self {
             mode: Mode::Normal,
             formatted_prompt: Text::raw(""),
             editor,
+            editing: None,
+            history: PromptHistory::default(),
+            notice: None,
+            vim: Vim::new(),
         }
     }
 }
 
 impl Prompt<'_> {
+    /// The prompt with the history of the previous sessions
     pub fn new() -> Self {
-        Self::default()
+        Self {
+            history: PromptHistory::load(),
+            ..Default::default()
+        }
     }
 
     pub fn clear(&mut self) {
         self.formatted_prompt = Text::raw("");
         self.editor.select_all();
         self.editor.cut();
+        self.history.reset();
+        self.notice = None;
     }
 
-    pub fn height(&self, frame_size: &Rect) -> u16 {
-        let prompt_block_max_height = (0.4 * frame_size.height as f32) as u16;
-
-        let height: u16 = 1 + self
-            .editor
-            .lines()
-            .iter()
-            .map(|line| 1 + line.width() as u16 / frame_size.width)
-            .sum::<u16>();
-
-        std::cmp::min(height, prompt_block_max_height)
+    // replace the text by a prompt of the history, without touching the yanked text
+    fn set_text(&mut self, text: &str) {
+        self.editor.select_all();
+        self.editor.delete_char();
+        self.editor.insert_str(text);
     }
 
-    pub fn handler(
-        &mut self,
-        key_event: KeyEvent,
-        previous_key: KeyCode,
-        clipboard: Option<&mut Clipboard>,
-    ) {
-        match self.mode {
-            Mode::Insert => match key_event.code {
-                KeyCode::Enter => {
-                    self.editor.insert_newline();
-                }
-
-                KeyCode::Char(c) => {
-                    self.editor.insert_char(c);
-                }
-
-                KeyCode::Backspace => {
-                    self.editor.delete_char();
-                }
-
-                KeyCode::Esc => {
-                    self.mode = Mode::Normal;
-                }
-                _ => {}
-            },
-            Mode::Normal | Mode::Visual => match key_event.code {
-                KeyCode::Char('i') => {
-                    self.mode = Mode::Insert;
-                }
-
-                KeyCode::Esc => {
-                    self.mode = Mode::Normal;
-                    self.editor.cancel_selection();
-                }
-
-                KeyCode::Char('v') => {
-                    self.mode = Mode::Visual;
-                    self.editor.start_selection();
-                }
-
-                KeyCode::Char('h') | KeyCode::Left if key_event.modifiers == KeyModifiers::NONE => {
-                    self.editor.move_cursor(CursorMove::Back);
-                }
-
-                KeyCode::Char('j') | KeyCode::Down if key_event.modifiers == KeyModifiers::NONE => {
-                    self.editor.move_cursor(CursorMove::Down);
-                }
-
-                KeyCode::Char('k') | KeyCode::Up if key_event.modifiers == KeyModifiers::NONE => {
-                    self.editor.move_cursor(CursorMove::Up);
-                }
-
-                KeyCode::Char('l') | KeyCode::Right
-                    if key_event.modifiers == KeyModifiers::NONE =>
-                {
-                    self.editor.move_cursor(CursorMove::Forward);
-                }
-
-                KeyCode::Char('w') => match previous_key {
-                    KeyCode::Char('d') => {
-                        self.editor.delete_next_word();
-                    }
-                    KeyCode::Char('c') => {
-                        self.editor.delete_next_word();
-                        self.mode = Mode::Insert;
-                    }
-
-                    _ => self.editor.move_cursor(CursorMove::WordForward),
-                },
+    /// Whether the history can be gone through with the keys, e.g. not in the middle of `3d`
+    pub fn can_recall(&self) -> bool {
+        !matches!(self.mode, Mode::Visual | Mode::VisualLine)
+            && self.vim.is_idle()
+            && !self.history.is_searching()
+    }
 
-                KeyCode::Char('b') => match previous_key {
-                    KeyCode::Char('d') => {
-                        self.editor.delete_word();
-                    }
-                    KeyCode::Char('c') => {
-                        self.editor.delete_word();
-                        self.mode = Mode::Insert;
-                    }
+    /// Recall the previous prompt of the history, or move up in a prompt of several lines
+    pub fn recall_previous(&mut self) {
+        if self.editor.cursor().0 > 0 {
+            self.editor.move_cursor(CursorMove::Up);
+            return;
+        }
 
-                    _ => self.editor.move_cursor(CursorMove::WordBack),
-                },
+        let current = self.editor.lines().join("\n");
+        if let Some(prompt) = self.history.older(&current).map(String::from) {
+            self.set_text(&prompt);
+        }
+    }
 
-                KeyCode::Char('$') => match previous_key {
-                    KeyCode::Char('d') => {
-                        self.editor.delete_line_by_end();
-                    }
-                    KeyCode::Char('c') => {
-                        self.editor.delete_line_by_end();
-                        self.mode = Mode::Insert;
-                    }
-                    _ => self.editor.move_cursor(CursorMove::End),
-                },
+    /// Recall the next prompt of the history, or move down in a prompt of several lines
+    pub fn recall_next(&mut self) {
+        if self.editor.cursor().0 + 1 < self.editor.lines().len() {
+            self.editor.move_cursor(CursorMove::Down);
+            return;
+        }
 
-                KeyCode::Char('0') => match previous_key {
-                    KeyCode::Char('d') => {
-                        self.editor.delete_line_by_head();
-                    }
-                    KeyCode::Char('c') => {
-                        self.editor.delete_line_by_head();
-                        self.mode = Mode::Insert;
-                    }
-                    _ => self.editor.move_cursor(CursorMove::Head),
-                },
+        if let Some(prompt) = self.history.newer() {
+            self.set_text(&prompt);
+        }
+    }
 
-                KeyCode::Char('G') => self.editor.move_cursor(CursorMove::Bottom),
+    /// Start a search in the history, or search an older prompt
+    pub fn search_history(&mut self) {
+        if self.history.is_searching() {
+            if let Some(prompt) = self.history.search_older().map(String::from) {
+                self.set_text(&prompt);
+            }
+        } else {
+            let current = self.editor.lines().join("\n");
+            self.history.start_search(&current);
+        }
+    }
 
-                KeyCode::Char('g') => {
-                    if previous_key == KeyCode::Char('g') {
-                        self.editor.move_cursor(CursorMove::Jump(0, 0))
-                    }
-                }
+    // a command being typed, with the cursor at its end
+    fn is_command(&self) -> bool {
+        let lines = self.editor.lines();
+        lines.len() == 1
+            && lines[0].starts_with('/')
+            && !lines[0].starts_with("//")
+            && self.editor.cursor().1 == lines[0].chars().count()
+    }
 
-                KeyCode::Char('D') => {
-                    self.editor.move_cursor(CursorMove::Head);
-                    self.editor.delete_line_by_end();
-                    self.editor.delete_line_by_head();
-                }
+    // the `@path` before the cursor, with the column where it starts
+    fn reference(&self) -> Option<(usize, String)> {
+        let (row, col) = self.editor.cursor();
+        let line: Vec<char> = self.editor.lines()[row].chars().collect();
+        if line.get(col).is_some_and(|c| !c.is_whitespace()) {
+            return None;
+        }
 
-                KeyCode::Char('d') => {
-                    if previous_key == KeyCode::Char('d') {
-                        self.editor.move_cursor(CursorMove::Head);
-                        self.editor.delete_line_by_end();
-                    }
-                }
+        let start = line[..col]
+            .iter()
+            .rposition(|c| c.is_whitespace())
+            .map_or(0, |space| space + 1);
+        let word: String = line[start..col].iter().collect();
+        word.starts_with('@').then_some((start, word))
+    }
 
-                KeyCode::Char('c') => {
-                    if previous_key == KeyCode::Char('c') {
-                        self.editor.move_cursor(CursorMove::Head);
-                        self.editor.delete_line_by_end();
-                        self.mode = Mode::Insert;
-                    }
-                }
+    /// A command or an `@path` can be completed, in Insert mode
+    pub fn can_complete(&self) -> bool {
+        self.mode == Mode::Insert && (self.is_command() || self.reference().is_some())
+    }
 
-                KeyCode::Char('C') => {
-                    self.editor.delete_line_by_end();
-                    self.mode = Mode::Insert;
+    /// Complete the command or the `@path`, the candidates are shown when there are several
+    pub fn complete(&mut self, models: &[String]) {
+        let input = self.editor.lines().join("\n");
+        let completion = match self.is_command() {
+            true => command::complete(&input, models),
+            false => None,
+        };
+
+        let completion = match completion {
+            Some(completion) => {
+                self.set_text(&completion.text);
+                completion
+            }
+            None => {
+                let Some((start, reference)) = self.reference() else {
+                    return;
+                };
+                let Some(completion) = attachment::complete(&reference) else {
+                    return;
+                };
+                for _ in start..self.editor.cursor().1 {
+                    self.editor.delete_char();
                 }
+                self.editor.insert_str(&completion.text);
+                completion
+            }
+        };
 
-                KeyCode::Char('x') => {
-                    self.editor.delete_next_char();
-                }
+        if !completion.candidates.is_empty() {
+            self.notice = Some((NotificationLevel::Info, completion.candidates.join("  ")));
+        }
+    }
 
-                KeyCode::Char('a') => {
-                    self.editor.move_cursor(CursorMove::Forward);
-                    self.mode = Mode::Insert;
+    // the keys typed while searching the history, `true` when the key is used
+    fn handle_history_search(&mut self, key_event: KeyEvent) -> bool {
+        let found = match key_event.code {
+            KeyCode::Char(c)
+                if !key_event
+                    .modifiers
+                    .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                self.history.search_push(c).map(String::from)
+            }
+            KeyCode::Backspace => self.history.search_pop().map(String::from),
+            KeyCode::Enter => {
+                self.history.accept_search();
+                return true;
+            }
+            KeyCode::Esc => {
+                if let Some(original) = self.history.cancel_search() {
+                    self.set_text(&original);
                 }
+                return true;
+            }
+            // the other keys are used on the prompt found
+            _ => {
+                self.history.accept_search();
+                return false;
+            }
+        };
+
+        if let Some(prompt) = found {
+            self.set_text(&prompt);
+        }
+        true
+    }
 
-                KeyCode::Char('A') => {
-                    self.editor.move_cursor(CursorMove::End);
-                    self.mode = Mode::Insert;
-                }
+    // insert the text pasted in the terminal as a single edit, without the control characters
+    pub fn paste(&mut self, text: &str) {
+        let text: String = text
+            .chars()
+            .filter(|c| *c == '\n' || *c == '\t' || !c.is_control())
+            .collect();
+        self.editor.insert_str(text);
+    }
 
-                KeyCode::Char('o') => {
-                    self.editor.move_cursor(CursorMove::End);
-                    self.editor.insert_newline();
-                    self.mode = Mode::Insert;
-                }
+    pub fn height(&self, frame_size: &Rect) -> u16 {
+        let prompt_block_max_height = (0.4 * frame_size.height as f32) as u16;
 
-                KeyCode::Char('O') => {
-                    self.editor.move_cursor(CursorMove::Head);
-                    self.editor.insert_newline();
-                    self.editor.move_cursor(CursorMove::Up);
-                    self.mode = Mode::Insert;
-                }
+        let height: u16 = 1 + self
+            .editor
+            .lines()
+            .iter()
+            .map(|line| 1 + line.width() as u16 / frame_size.width)
+            .sum::<u16>();
 
-                KeyCode::Char('I') => {
-                    self.editor.move_cursor(CursorMove::Head);
-                    self.mode = Mode::Insert;
-                }
+        std::cmp::min(height, prompt_block_max_height)
+    }
 
-                KeyCode::Char('y') => {
-                    self.editor.copy();
-                    if let Some(clipboard) = clipboard {
-                        let text = self.editor.yank_text();
-                        let _ = clipboard.set_text(text);
-                    }
-                }
+    /// The keys are text typed in the prompt, not shortcuts
+    pub fn is_typing(&self) -> bool {
+        matches!(self.mode, Mode::Insert | Mode::Replace)
+            || self.vim.is_typing()
+            || self.history.is_searching()
+    }
 
-                KeyCode::Char('p') => {
-                    if !self.editor.paste() {
-                        if let Some(clipboard) = clipboard {
-                            if let Ok(text) = clipboard.get_text() {
-                                self.editor.insert_str(text);
-                            }
-                        }
-                    }
-                }
+    pub fn handler(&mut self, key_event: KeyEvent, clipboard: Option<&mut Clipboard>) {
+        self.notice = None;
 
-                KeyCode::Char('u') => {
-                    self.editor.undo();
-                }
+        if self.history.is_searching() && self.handle_history_search(key_event) {
+            return;
+        }
 
-                _ => {}
-            },
+        // cancel the edit of a previous message
+        if key_event.code == KeyCode::Esc
+            && self.mode == Mode::Normal
+            && self.vim.is_idle()
+            && self.editing.is_some()
+        {
+            self.editing = None;
+            self.clear();
+            return;
         }
+
+        self.vim
+            .handle(key_event, &mut self.editor, &mut self.mode, clipboard);
     }
 
-    pub fn render(&mut self, frame: &mut Frame, block: Rect, focused_block: &FocusedBlock) {
+    pub fn render(
+        &mut self,
+        frame: &mut Frame,
+        block: Rect,
+        focused_block: &FocusedBlock,
+        theme: &Theme,
+    ) {
+        self.editor
+            .set_selection_style(Style::default().bg(theme.selection));
+
         frame.render_widget(
             Block::default()
+                .title(
+                    match (
+                        self.history.search_title(),
+                        self.vim.search_query(),
+                        &self.notice,
+                        self.editing,
+                    ) {
+                        (Some(title), _, _, _) => Line::raw(title),
+                        (None, Some(query), _, _) => Line::raw(format!(" /{} ", query)),
+                        (None, None, Some((level, notice)), _) => Line::styled(
+                            format!(" {} ", notice),
+                            match level {
+                                NotificationLevel::Error => Style::default().fg(theme.error),
+                                NotificationLevel::Warning => Style::default().fg(theme.warning),
+                                NotificationLevel::Info => Style::default().fg(theme.info),
+                            },
+                        ),
+                        (None, None, None, Some(_)) => Line::raw(" Edit message (Esc to cancel) "),
+                        (None, None, None, None) => Line::default(),
+                    },
+                )
                 .borders(Borders::all())
                 .border_style({
                     if *focused_block == FocusedBlock::Prompt {
                         match self.mode {
-                            Mode::Insert => Style::default().fg(Color::Green),
+                            Mode::Insert | Mode::Replace => Style::default().fg(theme.insert_mode),
                             Mode::Normal => Style::default(),
-                            Mode::Visual => Style::default().fg(Color::Yellow),
+                            Mode::Visual | Mode::VisualLine => {
+                                Style::default().fg(theme.visual_mode)
+                            }
                         }
                     } else {
                         Style::default()
@@ -278,9 +324,9 @@ impl Prompt<'_> {
                 })
                 .border_type({
                     if *focused_block == FocusedBlock::Prompt {
-                        BorderType::Thick
+                        theme.focused_border
                     } else {
-                        BorderType::Rounded
+                        theme.border
                     }
                 }),
             block,
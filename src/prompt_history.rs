@@ -0,0 +1,212 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+};
+
+use crate::{app::AppResult, history::data_directory};
+
+const PROMPT_HISTORY_FILE: &str = "tenere.prompts";
+
+// the oldest prompts are dropped past this
+const MAX_PROMPTS: usize = 1000;
+
+/// A search in the prompt history, like `ctrl + r` in a shell
+#[derive(Debug, Default)]
+struct PromptSearch {
+    query: String,
+    // index of the prompt found
+    found: Option<usize>,
+    failing: bool,
+    // the text of the prompt before the search, restored when it is cancelled
+    original: String,
+}
+
+/// The prompts submitted in all the sessions, the oldest first.
+/// They are saved in the data directory, one JSON string per line.
+#[derive(Debug, Default)]
+pub struct PromptHistory {
+    prompts: Vec<String>,
+    // index of the recalled prompt
+    position: Option<usize>,
+    // the text being typed before going through the history
+    draft: String,
+    search: Option<PromptSearch>,
+}
+
+impl PromptHistory {
+    fn path() -> PathBuf {
+        data_directory().join(PROMPT_HISTORY_FILE)
+    }
+
+    pub fn load() -> Self {
+        let mut prompts: Vec<String> = fs::read_to_string(Self::path())
+            .map(|text| {
+                text.lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if prompts.len() > MAX_PROMPTS {
+            prompts.drain(..prompts.len() - MAX_PROMPTS);
+        }
+
+        Self {
+            prompts,
+            ..Default::default()
+        }
+    }
+
+    /// Add a submitted prompt, unless it is the same as the previous one
+    pub fn push(&mut self, prompt: &str) -> AppResult<()> {
+        self.reset();
+
+        if self.prompts.last().is_some_and(|last| last == prompt) {
+            return Ok(());
+        }
+        self.prompts.push(prompt.to_string());
+
+        if self.prompts.len() > MAX_PROMPTS {
+            self.prompts.drain(..self.prompts.len() - MAX_PROMPTS);
+
+            let mut text = String::new();
+            for prompt in &self.prompts {
+                text.push_str(&serde_json::to_string(prompt)?);
+                text.push('\n');
+            }
+            fs::write(Self::path(), text)?;
+        } else {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(Self::path())?;
+            writeln!(file, "{}", serde_json::to_string(prompt)?)?;
+        }
+
+        Ok(())
+    }
+
+    /// Going through the history or searching it
+    pub fn is_active(&self) -> bool {
+        self.position.is_some() || self.search.is_some()
+    }
+
+    pub fn reset(&mut self) {
+        self.position = None;
+        self.draft.clear();
+        self.search = None;
+    }
+
+    /// The prompt before the recalled one, `current` is kept to come back to it
+    pub fn older(&mut self, current: &str) -> Option<&str> {
+        let position = match self.position {
+            None if self.prompts.is_empty() => return None,
+            None => {
+                self.draft = current.to_string();
+                self.prompts.len() - 1
+            }
+            Some(0) => return None,
+            Some(position) => position - 1,
+        };
+
+        self.position = Some(position);
+        Some(&self.prompts[position])
+    }
+
+    /// The prompt after the recalled one, then the text typed before
+    pub fn newer(&mut self) -> Option<String> {
+        let position = self.position? + 1;
+
+        if position < self.prompts.len() {
+            self.position = Some(position);
+            Some(self.prompts[position].clone())
+        } else {
+            self.position = None;
+            Some(std::mem::take(&mut self.draft))
+        }
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.search.is_some()
+    }
+
+    pub fn start_search(&mut self, current: &str) {
+        self.search = Some(PromptSearch {
+            original: current.to_string(),
+            ..Default::default()
+        });
+    }
+
+    /// The title of the prompt during the search
+    pub fn search_title(&self) -> Option<String> {
+        self.search.as_ref().map(|search| {
+            if search.failing {
+                format!(" failing search: {} ", search.query)
+            } else {
+                format!(" search: {} ", search.query)
+            }
+        })
+    }
+
+    // the most recent prompt before `before` that contains the query,
+    // the prompt found stays the same when there is none
+    fn search_from(&mut self, before: usize, skip_found: bool) -> Option<&str> {
+        let search = self.search.as_mut()?;
+        let found = search
+            .found
+            .filter(|_| skip_found)
+            .map(|index| &self.prompts[index]);
+
+        let index = self.prompts[..before]
+            .iter()
+            .rposition(|prompt| prompt.contains(&search.query) && Some(prompt) != found);
+
+        search.failing = index.is_none();
+        if index.is_some() {
+            search.found = index;
+        }
+        index.map(|index| self.prompts[index].as_str())
+    }
+
+    /// Add a char to the query, the prompt found is returned
+    pub fn search_push(&mut self, c: char) -> Option<&str> {
+        let search = self.search.as_mut()?;
+        search.query.push(c);
+
+        // the prompt found may still match
+        let before = search.found.map_or(self.prompts.len(), |found| found + 1);
+        self.search_from(before, false)
+    }
+
+    pub fn search_pop(&mut self) -> Option<&str> {
+        let search = self.search.as_mut()?;
+        search.query.pop();
+        search.found = None;
+
+        self.search_from(self.prompts.len(), false)
+    }
+
+    /// Search an older prompt with the same query, the ones with the text
+    /// of the prompt found are skipped
+    pub fn search_older(&mut self) -> Option<&str> {
+        let search = self.search.as_ref()?;
+        let before = search.found.unwrap_or(self.prompts.len());
+        self.search_from(before, true)
+    }
+
+    /// Keep the prompt found, the history is then gone through from it
+    pub fn accept_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            if search.found.is_some() {
+                self.position = search.found;
+                self.draft = search.original;
+            }
+        }
+    }
+
+    /// The text of the prompt before the search
+    pub fn cancel_search(&mut self) -> Option<String> {
+        self.search.take().map(|search| search.original)
+    }
+}
@@ -0,0 +1,169 @@
+use std::ops::Range;
+
+use ratatui::{
+    style::{Color, Style, Stylize},
+    text::{Line, Span},
+};
+
+use crate::theme::Theme;
+
+/// Search of a text in the chat, navigated with `n` and `N`
+#[derive(Debug, Default, Clone)]
+pub struct Search {
+    pub query: String,
+    // the query is being typed
+    pub editing: bool,
+    // index of the line and byte range of each match
+    pub matches: Vec<(usize, Range<usize>)>,
+    pub current: Option<usize>,
+    // line at the top of the view when the search started
+    origin: usize,
+}
+
+impl Search {
+    pub fn new(origin: usize) -> Self {
+        Self {
+            editing: true,
+            origin,
+            ..Default::default()
+        }
+    }
+
+    // the search is case sensitive only when the query has an uppercase letter
+    fn case_sensitive(&self) -> bool {
+        self.query.chars().any(|c| c.is_uppercase())
+    }
+
+    pub fn find<'l, 'b: 'l>(&mut self, lines: impl IntoIterator<Item = &'l Line<'b>>) {
+        self.matches.clear();
+
+        if self.query.is_empty() {
+            self.current = None;
+            return;
+        }
+
+        // ascii lowercase keeps the byte offsets of the text
+        let query = match self.case_sensitive() {
+            true => self.query.clone(),
+            false => self.query.to_ascii_lowercase(),
+        };
+
+        for (index, line) in lines.into_iter().enumerate() {
+            let mut text: String = line
+                .spans
+                .iter()
+                .map(|span| span.content.as_ref())
+                .collect();
+            if !self.case_sensitive() {
+                text.make_ascii_lowercase();
+            }
+
+            self.matches.extend(
+                text.match_indices(&query)
+                    .map(|(start, _)| (index, start..start + query.len())),
+            );
+        }
+
+        self.current = match self.matches.len() {
+            0 => None,
+            n => self.current.map(|current| current.min(n - 1)),
+        };
+    }
+
+    pub fn next(&mut self) {
+        if !self.matches.is_empty() {
+            self.current = Some(match self.current {
+                Some(current) => (current + 1) % self.matches.len(),
+                None => 0,
+            });
+        }
+    }
+
+    pub fn previous(&mut self) {
+        if !self.matches.is_empty() {
+            self.current = Some(match self.current {
+                Some(current) => (current + self.matches.len() - 1) % self.matches.len(),
+                None => self.matches.len() - 1,
+            });
+        }
+    }
+
+    // go to the first match from the origin of the search
+    pub fn reset(&mut self) {
+        self.current = self
+            .matches
+            .iter()
+            .position(|(line, _)| *line >= self.origin)
+            .or(if self.matches.is_empty() {
+                None
+            } else {
+                Some(0)
+            });
+    }
+
+    // line of the current match
+    pub fn line(&self) -> Option<usize> {
+        self.current.map(|current| self.matches[current].0)
+    }
+
+    pub fn highlight(&self, lines: &mut [Line], theme: &Theme) {
+        for (index, (line, range)) in self.matches.iter().enumerate() {
+            let style = match Some(index) == self.current {
+                true => Style::default().bg(theme.search_current).fg(Color::Black),
+                false => Style::default().bg(theme.search_match).fg(Color::Black),
+            };
+            if let Some(line) = lines.get_mut(*line) {
+                highlight_range(line, range, style);
+            }
+        }
+    }
+
+    pub fn status(&self, theme: &Theme) -> Line<'static> {
+        let mut status = Line::from(format!("/{}", self.query));
+
+        if self.editing {
+            status.push_span(Span::raw(" ").reversed());
+        }
+
+        status.push_span(match (self.current, self.matches.len()) {
+            (_, 0) if !self.query.is_empty() => Span::raw("  no match").fg(theme.error),
+            (Some(current), n) => Span::raw(format!("  {}/{}", current + 1, n)).fg(theme.dim),
+            _ => Span::raw(""),
+        });
+
+        status
+    }
+}
+
+// Restyle the byte range of the line, splitting the spans at its bounds
+fn highlight_range(line: &mut Line, range: &Range<usize>, style: Style) {
+    let mut spans = Vec::new();
+    let mut offset = 0;
+
+    for span in line.spans.drain(..) {
+        let end = offset + span.content.len();
+
+        if range.start >= end || range.end <= offset {
+            spans.push(span);
+        } else {
+            let start = range.start.max(offset) - offset;
+            let stop = range.end.min(end) - offset;
+            let content = span.content.as_ref();
+
+            if start > 0 {
+                spans.push(Span::styled(content[..start].to_string(), span.style));
+            }
+            spans.push(Span::styled(
+                content[start..stop].to_string(),
+                span.style.patch(style),
+            ));
+            if stop < content.len() {
+                spans.push(Span::styled(content[stop..].to_string(), span.style));
+            }
+        }
+
+        offset = end;
+    }
+
+    line.spans = spans;
+}
@@ -0,0 +1,32 @@
+use std::sync::{atomic::AtomicBool, Arc};
+
+use tokio::sync::Mutex;
+
+use crate::{chat::Chat, llm::LLM, spinner::Spinner};
+
+/// Conversation open in a tab, with its own backend and stream.
+/// The session of the active tab lives in the fields of the `App`.
+pub struct Session<'a> {
+    pub id: usize,
+    pub chat: Chat<'a>,
+    pub llm: Arc<Mutex<Box<dyn LLM + 'static>>>,
+    // e.g. `ollama/llama3`, it can be changed with `/model`
+    pub model: String,
+    pub temperature: Option<f64>,
+    pub terminate_response_signal: Arc<AtomicBool>,
+    pub spinner: Spinner,
+}
+
+impl Session<'_> {
+    pub fn new(id: usize, model: String, llm: Box<dyn LLM + 'static>) -> Self {
+        Self {
+            id,
+            chat: Chat::default(),
+            llm: Arc::new(Mutex::new(llm)),
+            model,
+            temperature: None,
+            terminate_response_signal: Arc::new(AtomicBool::new(false)),
+            spinner: Spinner::default(),
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use std::{ops::Range, process::Stdio, time::Duration};
+
+use tokio::process::Command;
+
+use crate::{attachment::fenced, markdown};
+
+// the commands still running after this are killed
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A command of the prompt, `!cmd` at the start of a line or `$(cmd)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShellCommand {
+    /// The text of the command in the prompt, replaced by its output
+    pub source: String,
+    /// The byte range of the source in the prompt
+    pub range: Range<usize>,
+    pub command: String,
+}
+
+// the text at the index starts a line of the prompt
+fn line_start(prompt: &str, index: usize) -> bool {
+    index == 0 || prompt[..index].ends_with('\n')
+}
+
+/// The commands of the prompt, the ones in code blocks and code spans are not run.
+/// `\!` at the start of a line and `\$(` are escaped, they are sent as text.
+pub fn commands(prompt: &str) -> Vec<ShellCommand> {
+    let mut commands = Vec::new();
+    // end of the line of the last `!` command, its code spans belong to the command
+    let mut command_end = 0;
+
+    for range in markdown::prose(prompt) {
+        if range.start < command_end {
+            continue;
+        }
+
+        if line_start(prompt, range.start) && prompt[range.clone()].starts_with('!') {
+            let line = prompt[range.start..].lines().next().unwrap_or_default();
+            command_end = range.start + line.len();
+
+            let command = line[1..].trim();
+            if !command.is_empty() {
+                commands.push(ShellCommand {
+                    source: line.to_string(),
+                    range: range.start..command_end,
+                    command: command.to_string(),
+                });
+            }
+            continue;
+        }
+
+        let text = &prompt[range.clone()];
+        let chars: Vec<(usize, char)> = text.char_indices().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i].1 {
+                // the escaped character is text
+                '\\' => i += 1,
+                '$' if chars.get(i + 1).map(|c| c.1) == Some('(') => {
+                    // the parenthesis closing the command
+                    let mut depth = 0;
+                    let end = (i + 1..chars.len()).find(|j| {
+                        match chars[*j].1 {
+                            '(' => depth += 1,
+                            ')' => depth -= 1,
+                            _ => {}
+                        }
+                        depth == 0
+                    });
+
+                    if let Some(end) = end {
+                        let command = text[chars[i + 1].0 + 1..chars[end].0].trim();
+                        if !command.is_empty() {
+                            commands.push(ShellCommand {
+                                source: text[chars[i].0..=chars[end].0].to_string(),
+                                range: range.start + chars[i].0..range.start + chars[end].0 + 1,
+                                command: command.to_string(),
+                            });
+                        }
+                        i = end;
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+
+    commands
+}
+
+/// The prompt without the backslashes escaping `\!` and `\$(`
+pub fn unescape(prompt: &str) -> String {
+    let mut escapes = Vec::new();
+    for range in markdown::prose(prompt) {
+        let text = &prompt[range.clone()];
+        if line_start(prompt, range.start) && text.starts_with("\\!") {
+            escapes.push(range.start);
+        }
+        escapes.extend(
+            text.match_indices("\\$(")
+                .map(|(index, _)| range.start + index),
+        );
+    }
+
+    let mut text = prompt.to_string();
+    for index in escapes.into_iter().rev() {
+        text.remove(index);
+    }
+    text
+}
+
+/// Run the command in the shell. The output is the stdout followed by the stderr,
+/// cut after `max_size` bytes.
+pub async fn run(command: &str, max_size: usize) -> String {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell.arg(command).stdin(Stdio::null()).kill_on_drop(true);
+
+    let mut text = match tokio::time::timeout(TIMEOUT, shell.output()).await {
+        Ok(Ok(output)) => {
+            let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+            text.push_str(&String::from_utf8_lossy(&output.stderr));
+            if !output.status.success() {
+                text.truncate(text.trim_end().len());
+                if !text.is_empty() {
+                    text.push('\n');
+                }
+                text.push_str(&format!("[{}]", output.status));
+            }
+            text
+        }
+        Ok(Err(e)) => format!("Can not run the command: {}", e),
+        Err(_) => format!(
+            "The command was stopped after {} seconds",
+            TIMEOUT.as_secs()
+        ),
+    };
+
+    if text.len() > max_size {
+        let end = (0..=max_size)
+            .rev()
+            .find(|end| text.is_char_boundary(*end))
+            .unwrap_or_default();
+        text.truncate(end);
+        text.push_str("\n[output cut]");
+    }
+    text
+}
+
+/// Replace the command in the prompt by its output in a code block.
+/// The commands are embedded from the last one, so that the ranges of the others are kept.
+pub fn embed(prompt: &str, command: &ShellCommand, output: &str) -> String {
+    let block = fenced(&format!("$ {}\n{}", command.command, output.trim_end()), "");
+
+    if prompt.get(command.range.clone()) != Some(command.source.as_str()) {
+        return format!("{}\n\n{}", prompt, block);
+    }
+
+    let block = match command.source.starts_with('!') {
+        true => block,
+        false => format!("\n{}\n", block),
+    };
+    let mut prompt = prompt.to_string();
+    prompt.replace_range(command.range.clone(), &block);
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(start: usize, source: &str, command: &str) -> ShellCommand {
+        ShellCommand {
+            source: source.to_string(),
+            range: start..start + source.len(),
+            command: command.to_string(),
+        }
+    }
+
+    #[test]
+    fn commands_of_the_prompt() {
+        let prompt = "why?\n!cargo build 2>&1\nthe diff: $(git diff $(git merge-base main HEAD)) and $( )\n!";
+        assert_eq!(
+            commands(prompt),
+            [
+                command(5, "!cargo build 2>&1", "cargo build 2>&1"),
+                command(
+                    33,
+                    "$(git diff $(git merge-base main HEAD))",
+                    "git diff $(git merge-base main HEAD)"
+                ),
+            ]
+        );
+
+        // the code spans of a `!` line are part of the command
+        assert_eq!(
+            commands("!echo `date` $(pwd)"),
+            [command(0, "!echo `date` $(pwd)", "echo `date` $(pwd)")]
+        );
+    }
+
+    #[test]
+    fn commands_in_code_are_not_run() {
+        let prompt = "```sh\n!ls\necho $(pwd)\n```\n~~~\n!ls\n~~~\nrun `$(pwd)` or ``!ls $(ls)``, then $(whoami)";
+        assert_eq!(commands(prompt), [command(74, "$(whoami)", "whoami")]);
+
+        // a code block that is not closed runs to the end
+        assert!(commands("```\n!ls\n$(pwd)").is_empty());
+    }
+
+    #[test]
+    fn escaped_commands() {
+        let prompt = "\\!ls\n !ls\nthe price is \\$(cost) not $(cost)\n`\\$(pwd)`";
+        assert_eq!(commands(prompt), [command(36, "$(cost)", "cost")]);
+        assert_eq!(
+            unescape(prompt),
+            "!ls\n !ls\nthe price is $(cost) not $(cost)\n`\\$(pwd)`"
+        );
+    }
+
+    #[test]
+    fn embed_the_output() {
+        let prompt = "!git status\nand $(pwd) here";
+        let [status, pwd] = <[ShellCommand; 2]>::try_from(commands(prompt)).unwrap();
+
+        let prompt = embed(prompt, &pwd, "/tmp\n");
+        assert_eq!(prompt, "!git status\nand \n```\n$ pwd\n/tmp\n```\n here");
+
+        let prompt = embed(&prompt, &status, "clean\n\n");
+        assert_eq!(
+            prompt,
+            "```\n$ git status\nclean\n```\nand \n```\n$ pwd\n/tmp\n```\n here"
+        );
+
+        // the command edited out of the prompt is added after it
+        assert_eq!(
+            embed("hello", &command(0, "$(ls)", "ls"), "a ```\n````"),
+            "hello\n\n`````\n$ ls\na ```\n````\n`````"
+        );
+    }
+
+    #[test]
+    fn embed_a_command_written_twice() {
+        let prompt = "why is `$(pwd)` empty?\n$(pwd)";
+        let commands = commands(prompt);
+        assert_eq!(commands, [command(23, "$(pwd)", "pwd")]);
+
+        assert_eq!(
+            embed(prompt, &commands[0], "/tmp\n"),
+            "why is `$(pwd)` empty?\n\n```\n$ pwd\n/tmp\n```\n"
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use ratatui::{
+    layout::{Alignment, Rect},
+    style::{Style, Stylize},
+    text::{Line, Span},
+    widgets::Paragraph,
+    Frame,
+};
+
+use crate::{app::App, llm::LLMRole, prompt::Mode};
+
+// a token is about 4 characters
+fn estimate(text: &str) -> usize {
+    text.len().div_ceil(4)
+}
+
+// Tokens the next request will take: the counts reported for the last answer,
+// and an estimation for the text after it
+fn context_tokens(app: &App) -> usize {
+    let messages = &app.chat.messages;
+
+    let last_counted = messages.iter().rposition(|message| {
+        message.role == LLMRole::ASSISTANT
+            && message
+                .metadata
+                .as_ref()
+                .is_some_and(|metadata| metadata.prompt_tokens.is_some())
+    });
+
+    let (counted, rest) = match last_counted {
+        Some(index) => {
+            let metadata = messages[index].metadata.as_ref().unwrap();
+            let tokens = metadata.prompt_tokens.unwrap_or_default()
+                + metadata.completion_tokens.unwrap_or_default();
+            (tokens as usize, &messages[index + 1..])
+        }
+        None => (0, &messages[..]),
+    };
+
+    counted
+        + rest
+            .iter()
+            .map(|message| estimate(&message.content))
+            .sum::<usize>()
+        + estimate(&app.chat.answer.plain_answer)
+        + estimate(&app.prompt.editor.lines().join("\n"))
+}
+
+fn format_tokens(tokens: usize) -> String {
+    if tokens < 1000 {
+        tokens.to_string()
+    } else {
+        format!("{:.1}k", tokens as f64 / 1000.0)
+    }
+}
+
+pub fn render(app: &App, frame: &mut Frame, area: Rect) {
+    let theme = &app.config.theme;
+
+    let mode = match app.prompt.mode {
+        Mode::Normal => Span::raw(" NORMAL ").bold().black().bg(theme.normal_mode),
+        Mode::Insert => Span::raw(" INSERT ").bold().black().bg(theme.insert_mode),
+        Mode::Visual => Span::raw(" VISUAL ").bold().black().bg(theme.visual_mode),
+        Mode::VisualLine => Span::raw(" V-LINE ").bold().black().bg(theme.visual_mode),
+        Mode::Replace => Span::raw(" REPLACE ").bold().black().bg(theme.insert_mode),
+    };
+
+    let tokens = context_tokens(app);
+    let context = match app.config.context_window(&app.model) {
+        Some(window) => {
+            let usage = tokens * 100 / window.max(1);
+            let style = match usage {
+                0..75 => Style::default(),
+                75..90 => Style::default().fg(theme.warning),
+                _ => Style::default().fg(theme.error),
+            };
+            Span::styled(
+                format!(
+                    "~{} / {} tokens ({}%)",
+                    format_tokens(tokens),
+                    format_tokens(window),
+                    usage
+                ),
+                style,
+            )
+        }
+        None => Span::raw(format!("~{} tokens", format_tokens(tokens))),
+    };
+
+    let left = Line::from(vec![
+        mode,
+        Span::raw(" "),
+        Span::raw(app.model.as_str()).bold(),
+        Span::raw(" │ ").fg(theme.dim),
+        context,
+    ]);
+
+    let mut right = Vec::new();
+    if app.chat.streaming {
+        right.push(Span::raw("● streaming").fg(theme.warning));
+    }
+    if !app.history.is_saved(&app.chat) {
+        if !right.is_empty() {
+            right.push(Span::raw(" │ ").fg(theme.dim));
+        }
+        right.push(Span::raw("unsaved").italic());
+    }
+    right.push(Span::raw(" "));
+
+    frame.render_widget(Paragraph::new(left), area);
+    frame.render_widget(
+        Paragraph::new(Line::from(right)).alignment(Alignment::Right),
+        area,
+    );
+}
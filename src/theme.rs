@@ -0,0 +1,190 @@
+use bat::theme::{default_theme, ColorScheme};
+use ratatui::{style::Color, widgets::BorderType};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemePreset {
+    #[default]
+    Dark,
+    Light,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Border {
+    Plain,
+    Rounded,
+    Double,
+    Thick,
+}
+
+impl From<Border> for BorderType {
+    fn from(border: Border) -> Self {
+        match border {
+            Border::Plain => BorderType::Plain,
+            Border::Rounded => BorderType::Rounded,
+            Border::Double => BorderType::Double,
+            Border::Thick => BorderType::Thick,
+        }
+    }
+}
+
+/// Colors and borders of the interface, and the theme of the code blocks
+#[derive(Deserialize, Debug, Clone)]
+#[serde(from = "ThemeConfig")]
+pub struct Theme {
+    // border and title of the focused block
+    pub focus: Color,
+    // background of the selected message, chat or text
+    pub selection: Color,
+    // hints, timestamps and separators
+    pub dim: Color,
+    // keys in the help, list markers and the other highlighted words
+    pub accent: Color,
+    // text of the help and of the active tab
+    pub text: Color,
+
+    pub normal_mode: Color,
+    pub insert_mode: Color,
+    pub visual_mode: Color,
+
+    pub info: Color,
+    pub warning: Color,
+    pub error: Color,
+
+    pub search_match: Color,
+    pub search_current: Color,
+
+    // markdown
+    pub heading: Color,
+    pub subheading: Color,
+    pub inline_code: Color,
+    pub link: Color,
+
+    pub border: BorderType,
+    pub focused_border: BorderType,
+
+    // name of a bat theme, see `bat --list-themes`
+    pub syntax_theme: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            focus: Color::Green,
+            selection: Color::DarkGray,
+            dim: Color::DarkGray,
+            accent: Color::Yellow,
+            text: Color::White,
+            normal_mode: Color::Blue,
+            insert_mode: Color::Green,
+            visual_mode: Color::Yellow,
+            info: Color::Green,
+            warning: Color::Yellow,
+            error: Color::Red,
+            search_match: Color::Yellow,
+            search_current: Color::LightRed,
+            heading: Color::Cyan,
+            subheading: Color::LightCyan,
+            inline_code: Color::Yellow,
+            link: Color::Blue,
+            border: BorderType::Rounded,
+            focused_border: BorderType::Thick,
+            syntax_theme: default_theme(ColorScheme::Dark).to_string(),
+        }
+    }
+
+    // yellow and light colors are hard to read on a light background
+    pub fn light() -> Self {
+        Self {
+            focus: Color::Blue,
+            selection: Color::Gray,
+            dim: Color::DarkGray,
+            accent: Color::Magenta,
+            text: Color::Black,
+            normal_mode: Color::Blue,
+            insert_mode: Color::Green,
+            visual_mode: Color::Magenta,
+            info: Color::Green,
+            warning: Color::Magenta,
+            error: Color::Red,
+            search_match: Color::Yellow,
+            search_current: Color::LightRed,
+            heading: Color::Blue,
+            subheading: Color::Magenta,
+            inline_code: Color::Red,
+            link: Color::Blue,
+            border: BorderType::Rounded,
+            focused_border: BorderType::Thick,
+            syntax_theme: default_theme(ColorScheme::Light).to_string(),
+        }
+    }
+}
+
+// The `[theme]` section: a preset, and the settings replacing the ones of the preset
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct ThemeConfig {
+    preset: ThemePreset,
+    focus: Option<Color>,
+    selection: Option<Color>,
+    dim: Option<Color>,
+    accent: Option<Color>,
+    text: Option<Color>,
+    normal_mode: Option<Color>,
+    insert_mode: Option<Color>,
+    visual_mode: Option<Color>,
+    info: Option<Color>,
+    warning: Option<Color>,
+    error: Option<Color>,
+    search_match: Option<Color>,
+    search_current: Option<Color>,
+    heading: Option<Color>,
+    subheading: Option<Color>,
+    inline_code: Option<Color>,
+    link: Option<Color>,
+    border: Option<Border>,
+    focused_border: Option<Border>,
+    syntax_theme: Option<String>,
+}
+
+impl From<ThemeConfig> for Theme {
+    fn from(config: ThemeConfig) -> Self {
+        let preset = match config.preset {
+            ThemePreset::Dark => Theme::dark(),
+            ThemePreset::Light => Theme::light(),
+        };
+
+        Self {
+            focus: config.focus.unwrap_or(preset.focus),
+            selection: config.selection.unwrap_or(preset.selection),
+            dim: config.dim.unwrap_or(preset.dim),
+            accent: config.accent.unwrap_or(preset.accent),
+            text: config.text.unwrap_or(preset.text),
+            normal_mode: config.normal_mode.unwrap_or(preset.normal_mode),
+            insert_mode: config.insert_mode.unwrap_or(preset.insert_mode),
+            visual_mode: config.visual_mode.unwrap_or(preset.visual_mode),
+            info: config.info.unwrap_or(preset.info),
+            warning: config.warning.unwrap_or(preset.warning),
+            error: config.error.unwrap_or(preset.error),
+            search_match: config.search_match.unwrap_or(preset.search_match),
+            search_current: config.search_current.unwrap_or(preset.search_current),
+            heading: config.heading.unwrap_or(preset.heading),
+            subheading: config.subheading.unwrap_or(preset.subheading),
+            inline_code: config.inline_code.unwrap_or(preset.inline_code),
+            link: config.link.unwrap_or(preset.link),
+            border: config.border.map_or(preset.border, BorderType::from),
+            focused_border: config
+                .focused_border
+                .map_or(preset.focused_border, BorderType::from),
+            syntax_theme: config.syntax_theme.unwrap_or(preset.syntax_theme),
+        }
+    }
+}
@@ -1,7 +1,10 @@
 use crate::app::{App, AppResult};
+use crate::autosave::Autosave;
 use crate::event::EventHandler;
 use crate::ui;
-use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+use crossterm::event::{
+    DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+};
 use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
 use ratatui::backend::Backend;
 use ratatui::Terminal;
@@ -19,12 +22,23 @@ impl<B: Backend> Tui<B> {
         Self { terminal, events }
     }
 
-    pub fn init(&mut self) -> AppResult<()> {
+    fn enter(mouse: bool) -> AppResult<()> {
         terminal::enable_raw_mode()?;
-        crossterm::execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+        crossterm::execute!(io::stdout(), EnterAlternateScreen, EnableBracketedPaste)?;
+        // without the capture, the terminal keeps its own text selection
+        if mouse {
+            crossterm::execute!(io::stdout(), EnableMouseCapture)?;
+        }
+        Ok(())
+    }
+
+    pub fn init(&mut self, autosave: Autosave, mouse: bool) -> AppResult<()> {
+        Self::enter(mouse)?;
 
         let panic_hook = panic::take_hook();
         panic::set_hook(Box::new(move |panic| {
+            // save the chats of the tabs before they are lost
+            let _ = autosave.write();
             Self::reset().expect("failed to reset the terminal");
             panic_hook(panic);
         }));
@@ -41,7 +55,26 @@ impl<B: Backend> Tui<B> {
 
     fn reset() -> AppResult<()> {
         terminal::disable_raw_mode()?;
-        crossterm::execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture)?;
+        crossterm::execute!(
+            io::stdout(),
+            LeaveAlternateScreen,
+            DisableMouseCapture,
+            DisableBracketedPaste
+        )?;
+        Ok(())
+    }
+
+    /// Give the terminal back, e.g. to run an editor
+    pub async fn suspend(&mut self) -> AppResult<()> {
+        self.events.pause().await;
+        self.exit()
+    }
+
+    pub fn resume(&mut self, mouse: bool) -> AppResult<()> {
+        Self::enter(mouse)?;
+        self.terminal.hide_cursor()?;
+        self.terminal.clear()?;
+        self.events.resume();
         Ok(())
     }
 
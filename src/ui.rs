@@ -1,44 +1,110 @@
 use std;
 
-use crate::app::{App, FocusedBlock};
+use crate::{
+    app::{App, FocusedBlock},
+    chat::Chat,
+    status,
+};
 use ratatui::{
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Style, Stylize},
+    text::Line,
+    widgets::Tabs,
     Frame,
 };
 
 pub type AppResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+fn render_tabs(app: &App, frame: &mut Frame, area: Rect) {
+    let theme = &app.config.theme;
+
+    let (before, after) = app.tabs.split_at(app.tab);
+
+    let title = |chat: &Chat| -> Line {
+        // a dot on the tabs that are streaming in the background
+        match chat.streaming {
+            true => Line::from(vec!["● ".fg(theme.warning), chat.title().into()]),
+            false => Line::from(chat.title()),
+        }
+    };
+
+    let titles = before
+        .iter()
+        .map(|session| title(&session.chat))
+        .chain([Line::from(app.chat.title())])
+        .chain(after.iter().map(|session| title(&session.chat)));
+
+    let tabs = Tabs::new(titles)
+        .select(app.tab)
+        .style(Style::default().fg(theme.dim))
+        .highlight_style(Style::default().fg(theme.text).bold().reversed());
+
+    frame.render_widget(tabs, area);
+}
+
 pub fn render(app: &mut App, frame: &mut Frame) {
+    let config = app.config.clone();
+    let theme = &config.theme;
+
     let frame_size = frame.area();
 
     let prompt_block_height = app.prompt.height(&frame_size) + 3;
 
-    let (chat_block, prompt_block) = {
+    let (chat_block, prompt_block, status_block) = {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
-            .constraints([Constraint::Min(1), Constraint::Length(prompt_block_height)].as_ref())
+            .constraints(
+                [
+                    Constraint::Min(1),
+                    Constraint::Length(prompt_block_height),
+                    Constraint::Length(1),
+                ]
+                .as_ref(),
+            )
             .split(frame.area());
-        (chunks[0], chunks[1])
+        (chunks[0], chunks[1], chunks[2])
+    };
+
+    // Tabs
+    let chat_block = if app.tab_count() > 1 {
+        let [tabs_block, chat_block] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(1)]).areas(chat_block);
+        render_tabs(app, frame, tabs_block);
+        chat_block
+    } else {
+        chat_block
     };
 
+    app.chat_area = chat_block;
+    app.prompt_area = prompt_block;
+
     // Chat
-    app.chat.render(frame, chat_block);
+    app.chat.render(frame, chat_block, theme, &config.keymap);
 
     // Prompt
-    app.prompt.render(frame, prompt_block, &app.focused_block);
+    app.prompt
+        .render(frame, prompt_block, &app.focused_block, theme);
+
+    // Status bar
+    status::render(app, frame, status_block);
 
     // History
     if let FocusedBlock::History | FocusedBlock::Preview = app.focused_block {
-        app.history.render(frame, &app.focused_block);
+        app.history.render(frame, &app.focused_block, theme);
     }
 
     // Help
     if let FocusedBlock::Help = app.focused_block {
-        app.help.render(frame);
+        app.help.render(frame, theme);
+    }
+
+    // Confirmation
+    if let Some(confirmation) = &app.confirmation {
+        confirmation.render(frame, theme, &config.keymap);
     }
 
     // Notifications
     for (index, notification) in app.notifications.iter().enumerate() {
-        notification.render(index, frame);
+        notification.render(index, frame, theme);
     }
 }
@@ -0,0 +1,1464 @@
+use std::collections::HashMap;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tui_textarea::{CursorMove, TextArea};
+
+use crate::{clipboard::Clipboard, prompt::Mode};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+// An operator with its count, e.g. `3d`
+type PendingOperator = Option<(Operator, usize)>;
+
+/// Start of a command, waiting for its next key
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum Pending {
+    #[default]
+    None,
+    // `"`, waiting for the name of the register
+    Register,
+    // `d`, `c` or `y`, waiting for a motion or a text object
+    Operator(Operator, usize),
+    // `g`, waiting for the second `g`
+    G(PendingOperator),
+    // `i` or `a` after an operator or in visual mode, waiting for the object
+    TextObject(PendingOperator, bool),
+    // `r`, waiting for the character replacing the ones under the cursor
+    Replace(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Motion {
+    Left,
+    Right,
+    Down,
+    Up,
+    WordForward(bool),
+    WordBackward(bool),
+    WordEnd(bool),
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    // first line, or the line of the count
+    Top,
+    // last line, or the line of the count
+    Bottom,
+}
+
+impl Motion {
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            'h' => Some(Self::Left),
+            'l' => Some(Self::Right),
+            'j' => Some(Self::Down),
+            'k' => Some(Self::Up),
+            'w' => Some(Self::WordForward(false)),
+            'W' => Some(Self::WordForward(true)),
+            'b' => Some(Self::WordBackward(false)),
+            'B' => Some(Self::WordBackward(true)),
+            'e' => Some(Self::WordEnd(false)),
+            'E' => Some(Self::WordEnd(true)),
+            '0' => Some(Self::LineStart),
+            '^' => Some(Self::FirstNonBlank),
+            '$' => Some(Self::LineEnd),
+            'G' => Some(Self::Bottom),
+            _ => None,
+        }
+    }
+}
+
+// Text an operator applies to
+#[derive(Debug, Clone, Copy)]
+enum Range {
+    // start and end (excluded) indexes in the buffer
+    Chars(usize, usize),
+    // first and last rows
+    Lines(usize, usize),
+}
+
+#[derive(Debug, Clone, Default)]
+struct Register {
+    text: String,
+    linewise: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Class {
+    Blank,
+    Word,
+    Punctuation,
+}
+
+// `big` words are the sequences of non-blank characters, like `W`
+fn class(c: char, big: bool) -> Class {
+    if c.is_whitespace() {
+        Class::Blank
+    } else if big || c.is_alphanumeric() || c == '_' {
+        Class::Word
+    } else {
+        Class::Punctuation
+    }
+}
+
+// The text as a sequence of characters, the lines separated by `\n`
+struct Buffer {
+    chars: Vec<char>,
+    // index of the first character of each line
+    starts: Vec<usize>,
+}
+
+impl Buffer {
+    fn new(lines: &[String]) -> Self {
+        let mut chars = Vec::new();
+        let mut starts = Vec::new();
+        for (row, line) in lines.iter().enumerate() {
+            if row > 0 {
+                chars.push('\n');
+            }
+            starts.push(chars.len());
+            chars.extend(line.chars());
+        }
+        Self { chars, starts }
+    }
+
+    fn index(&self, (row, col): (usize, usize)) -> usize {
+        (self.starts[row] + col).min(self.line_end(row))
+    }
+
+    fn position(&self, index: usize) -> (usize, usize) {
+        let row = self.starts.partition_point(|start| *start <= index) - 1;
+        (row, index - self.starts[row])
+    }
+
+    // index of the end of the line, before its `\n`
+    fn line_end(&self, row: usize) -> usize {
+        self.starts
+            .get(row + 1)
+            .map_or(self.chars.len(), |start| start - 1)
+    }
+
+    fn first_non_blank(&self, row: usize) -> usize {
+        (self.starts[row]..self.line_end(row))
+            .find(|index| !self.chars[*index].is_whitespace())
+            .unwrap_or(self.line_end(row))
+    }
+
+    fn last_row(&self) -> usize {
+        self.starts.len() - 1
+    }
+
+    fn word_forward(&self, mut index: usize, big: bool) -> usize {
+        let n = self.chars.len();
+        if index < n {
+            let start = class(self.chars[index], big);
+            if start != Class::Blank {
+                while index < n && class(self.chars[index], big) == start {
+                    index += 1;
+                }
+            }
+        }
+        while index < n && self.chars[index].is_whitespace() {
+            // an empty line is a word
+            if self.chars[index] == '\n' && self.chars.get(index + 1) == Some(&'\n') {
+                return index + 1;
+            }
+            index += 1;
+        }
+        index
+    }
+
+    fn word_backward(&self, index: usize, big: bool) -> usize {
+        if index == 0 {
+            return 0;
+        }
+        let mut index = index - 1;
+        while index > 0 && self.chars[index].is_whitespace() {
+            if self.chars[index] == '\n' && self.chars[index - 1] == '\n' {
+                return index;
+            }
+            index -= 1;
+        }
+        let start = class(self.chars[index], big);
+        while index > 0 && class(self.chars[index - 1], big) == start {
+            index -= 1;
+        }
+        index
+    }
+
+    // index of the last character of the word
+    fn word_end(&self, index: usize, big: bool) -> usize {
+        let n = self.chars.len();
+        let mut index = index + 1;
+        while index < n && self.chars[index].is_whitespace() {
+            index += 1;
+        }
+        if index >= n {
+            return n.saturating_sub(1);
+        }
+        let end = class(self.chars[index], big);
+        while index + 1 < n && class(self.chars[index + 1], big) == end {
+            index += 1;
+        }
+        index
+    }
+
+    // `iw` and `aw`: the word, with the blanks after it or else before it
+    fn word_object(&self, index: usize, inner: bool, big: bool) -> Option<(usize, usize)> {
+        let n = self.chars.len();
+        if index >= n || self.chars[index] == '\n' {
+            return None;
+        }
+
+        let object = class(self.chars[index], big);
+        let same = |i: usize| self.chars[i] != '\n' && class(self.chars[i], big) == object;
+        let blank = |i: usize| self.chars[i] == ' ' || self.chars[i] == '\t';
+
+        let mut start = index;
+        while start > 0 && same(start - 1) {
+            start -= 1;
+        }
+        let mut end = index + 1;
+        while end < n && same(end) {
+            end += 1;
+        }
+
+        if !inner {
+            if object == Class::Blank {
+                // the blanks and the word after them
+                if end < n && self.chars[end] != '\n' {
+                    let next = class(self.chars[end], big);
+                    while end < n && self.chars[end] != '\n' && class(self.chars[end], big) == next
+                    {
+                        end += 1;
+                    }
+                }
+            } else {
+                let word_end = end;
+                while end < n && blank(end) {
+                    end += 1;
+                }
+                if end == word_end {
+                    while start > 0 && blank(start - 1) {
+                        start -= 1;
+                    }
+                }
+            }
+        }
+
+        Some((start, end))
+    }
+
+    // `i"` and `a"`: the quoted text of the line, at or after the cursor
+    fn quote_object(&self, index: usize, quote: char, inner: bool) -> Option<(usize, usize)> {
+        let (row, col) = self.position(index);
+        let start = self.starts[row];
+        let quotes: Vec<usize> = (start..self.line_end(row))
+            .filter(|i| self.chars[*i] == quote)
+            .map(|i| i - start)
+            .collect();
+
+        let (open, close) = quotes
+            .chunks_exact(2)
+            .map(|pair| (pair[0], pair[1]))
+            .find(|(_, close)| col <= *close)?;
+
+        match inner {
+            true => Some((start + open + 1, start + close)),
+            false => Some((start + open, start + close + 1)),
+        }
+    }
+
+    // `i(` and `a(`: the text between the brackets around the cursor
+    fn bracket_object(
+        &self,
+        index: usize,
+        open: char,
+        close: char,
+        inner: bool,
+    ) -> Option<(usize, usize)> {
+        let n = self.chars.len();
+        if n == 0 {
+            return None;
+        }
+        let index = index.min(n - 1);
+
+        // the cursor can be on one of the brackets
+        let mut start = index;
+        let mut depth = 0;
+        if self.chars[index] != open {
+            if self.chars[index] == close {
+                start = start.checked_sub(1)?;
+            }
+            loop {
+                match self.chars[start] {
+                    c if c == open && depth == 0 => break,
+                    c if c == open => depth -= 1,
+                    c if c == close => depth += 1,
+                    _ => {}
+                }
+                start = start.checked_sub(1)?;
+            }
+        }
+
+        let mut end = start + 1;
+        depth = 0;
+        loop {
+            match *self.chars.get(end)? {
+                c if c == close && depth == 0 => break,
+                c if c == close => depth -= 1,
+                c if c == open => depth += 1,
+                _ => {}
+            }
+            end += 1;
+        }
+
+        if !inner {
+            return Some((start, end + 1));
+        }
+
+        // the lines between the brackets of a block, without the indentation of the last one
+        let mut inner_start = start + 1;
+        let mut inner_end = end;
+        if self.chars.get(inner_start) == Some(&'\n') {
+            inner_start += 1;
+            let mut i = inner_end;
+            while i > inner_start && (self.chars[i - 1] == ' ' || self.chars[i - 1] == '\t') {
+                i -= 1;
+            }
+            if i > inner_start && self.chars[i - 1] == '\n' {
+                inner_end = i;
+            }
+        }
+
+        Some((inner_start, inner_end.max(inner_start)))
+    }
+
+    fn text_object(&self, index: usize, c: char, inner: bool) -> Option<(usize, usize)> {
+        match c {
+            'w' => self.word_object(index, inner, false),
+            'W' => self.word_object(index, inner, true),
+            '"' | '\'' | '`' => self.quote_object(index, c, inner),
+            '(' | ')' | 'b' => self.bracket_object(index, '(', ')', inner),
+            '[' | ']' => self.bracket_object(index, '[', ']', inner),
+            '{' | '}' | 'B' => self.bracket_object(index, '{', '}', inner),
+            '<' | '>' => self.bracket_object(index, '<', '>', inner),
+            _ => None,
+        }
+    }
+}
+
+fn jump(editor: &mut TextArea, (row, col): (usize, usize)) {
+    editor.move_cursor(CursorMove::Jump(
+        row.try_into().unwrap_or(u16::MAX),
+        col.try_into().unwrap_or(u16::MAX),
+    ));
+}
+
+fn select(editor: &mut TextArea, start: (usize, usize), end: (usize, usize)) {
+    editor.cancel_selection();
+    jump(editor, start);
+    editor.start_selection();
+    jump(editor, end);
+}
+
+// Position of the next or previous match, the search is case sensitive only
+// when the query has an uppercase letter
+fn find(
+    lines: &[String],
+    query: &str,
+    from: (usize, usize),
+    forward: bool,
+) -> Option<(usize, usize)> {
+    let case_sensitive = query.chars().any(|c| c.is_uppercase());
+    let query = match case_sensitive {
+        true => query.to_string(),
+        false => query.to_ascii_lowercase(),
+    };
+
+    let matches: Vec<(usize, usize)> = lines
+        .iter()
+        .enumerate()
+        .flat_map(|(row, line)| {
+            let line = match case_sensitive {
+                true => line.clone(),
+                false => line.to_ascii_lowercase(),
+            };
+            line.match_indices(&query)
+                .map(|(start, _)| (row, line[..start].chars().count()))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    let found = match forward {
+        true => matches
+            .iter()
+            .find(|position| **position > from)
+            .or(matches.first()),
+        false => matches
+            .iter()
+            .rev()
+            .find(|position| **position < from)
+            .or(matches.last()),
+    };
+    found.copied()
+}
+
+/// Vim commands of the prompt: counts, operators with motions and text objects,
+/// registers, search and the repetition of the last change
+#[derive(Debug, Default)]
+pub struct Vim {
+    pending: Pending,
+    count: Option<usize>,
+    // register chosen with `"` for the next command
+    register: Option<char>,
+    registers: HashMap<char, Register>,
+    // the unnamed register is the yank buffer of the editor, also set by the copies of the chat
+    unnamed: Register,
+    // keys of the command being typed, and of the last change for `.`
+    keys: Vec<KeyEvent>,
+    last_change: Vec<KeyEvent>,
+    // the text typed after a change like `cw` is part of it
+    inserting: bool,
+    // query being typed after `/`
+    search: Option<String>,
+    last_search: Option<String>,
+    // row where the linewise selection started
+    visual_row: usize,
+}
+
+impl Vim {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The next keys are characters of the command, not shortcuts
+    pub fn is_typing(&self) -> bool {
+        self.search.is_some()
+            || matches!(
+                self.pending,
+                Pending::Register | Pending::TextObject(..) | Pending::Replace(_)
+            )
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.pending == Pending::None && self.count.is_none() && self.search.is_none()
+    }
+
+    pub fn search_query(&self) -> Option<&str> {
+        self.search.as_deref()
+    }
+
+    fn reset(&mut self) {
+        self.pending = Pending::None;
+        self.count = None;
+        self.register = None;
+        if !self.inserting {
+            self.keys.clear();
+        }
+    }
+
+    // the command is complete
+    fn finish(&mut self, changed: bool, mode: &Mode) {
+        self.pending = Pending::None;
+        self.count = None;
+        self.register = None;
+
+        if changed && !self.keys.is_empty() {
+            match mode {
+                Mode::Insert | Mode::Replace => self.inserting = true,
+                _ => self.last_change = std::mem::take(&mut self.keys),
+            }
+        } else if !self.inserting {
+            self.keys.clear();
+        }
+    }
+
+    pub fn handle(
+        &mut self,
+        key_event: KeyEvent,
+        editor: &mut TextArea,
+        mode: &mut Mode,
+        clipboard: Option<&mut Clipboard>,
+    ) {
+        if self.search.is_some() {
+            self.handle_search(key_event, editor);
+            return;
+        }
+
+        if let Mode::Insert | Mode::Replace = mode {
+            self.handle_insert(key_event, editor, mode);
+            return;
+        }
+
+        if *mode == Mode::Normal {
+            self.keys.push(key_event);
+        }
+
+        let text = !key_event
+            .modifiers
+            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+
+        let c = match key_event.code {
+            KeyCode::Char(c) if text => c,
+            KeyCode::Left if text => 'h',
+            KeyCode::Down if text => 'j',
+            KeyCode::Up if text => 'k',
+            KeyCode::Right if text => 'l',
+
+            KeyCode::Char('r') if key_event.modifiers == KeyModifiers::CONTROL => {
+                for _ in 0..self.count.unwrap_or(1) {
+                    editor.redo();
+                }
+                self.finish(false, mode);
+                return;
+            }
+
+            KeyCode::Esc => {
+                if *mode != Mode::Normal {
+                    editor.cancel_selection();
+                    *mode = Mode::Normal;
+                }
+                self.reset();
+                return;
+            }
+
+            _ => {
+                self.reset();
+                return;
+            }
+        };
+
+        // counts, before or after the operator
+        let counting = matches!(self.pending, Pending::None | Pending::Operator(..));
+        if counting && (c.is_ascii_digit() && (c != '0' || self.count.is_some())) {
+            let digit = c.to_digit(10).unwrap() as usize;
+            self.count = Some(
+                self.count
+                    .unwrap_or_default()
+                    .saturating_mul(10)
+                    .saturating_add(digit),
+            );
+            return;
+        }
+
+        match std::mem::take(&mut self.pending) {
+            Pending::Register => {
+                if c.is_ascii_alphabetic() || c == '+' || c == '"' {
+                    self.register = Some(c);
+                } else {
+                    self.reset();
+                }
+            }
+
+            Pending::Replace(count) => {
+                let changed = self.replace_chars(c, count, editor);
+                self.finish(changed, mode);
+            }
+
+            Pending::TextObject(operator, inner) => {
+                let buffer = Buffer::new(editor.lines());
+                let index = buffer.index(editor.cursor());
+                let changed = match buffer.text_object(index, c, inner) {
+                    Some((start, end)) => match operator {
+                        Some((operator, _)) => {
+                            self.apply(operator, Range::Chars(start, end), editor, mode, clipboard)
+                        }
+                        // the object is selected in visual mode
+                        None => {
+                            if end > start {
+                                select(editor, buffer.position(start), buffer.position(end - 1));
+                                *mode = Mode::Visual;
+                            }
+                            false
+                        }
+                    },
+                    None => false,
+                };
+                self.finish(changed, mode);
+            }
+
+            Pending::G(operator) => {
+                if c == 'g' {
+                    self.motion(Motion::Top, operator, editor, mode, clipboard);
+                } else {
+                    self.reset();
+                }
+            }
+
+            Pending::Operator(operator, count) => match c {
+                'i' | 'a' => {
+                    self.pending =
+                        Pending::TextObject(Some((operator, self.total(count))), c == 'i');
+                }
+                'g' => {
+                    self.pending = Pending::G(Some((operator, count)));
+                }
+                // `dd`, `cc` and `yy`
+                'd' | 'c' | 'y' if Self::operator(c) == Some(operator) => {
+                    let buffer = Buffer::new(editor.lines());
+                    let row = editor.cursor().0;
+                    let last = (row + self.total(count) - 1).min(buffer.last_row());
+                    let changed =
+                        self.apply(operator, Range::Lines(row, last), editor, mode, clipboard);
+                    self.finish(changed, mode);
+                }
+                c => match Motion::from_char(c) {
+                    Some(motion) => {
+                        self.motion(motion, Some((operator, count)), editor, mode, clipboard)
+                    }
+                    None => self.reset(),
+                },
+            },
+
+            Pending::None => match mode {
+                Mode::Visual | Mode::VisualLine => self.visual(c, editor, mode, clipboard),
+                _ => self.normal(c, editor, mode, clipboard),
+            },
+        }
+    }
+
+    fn operator(c: char) -> Option<Operator> {
+        match c {
+            'd' => Some(Operator::Delete),
+            'c' => Some(Operator::Change),
+            'y' => Some(Operator::Yank),
+            _ => None,
+        }
+    }
+
+    // the count of the operator, times the count of the motion
+    fn total(&mut self, count: usize) -> usize {
+        count.saturating_mul(self.count.take().unwrap_or(1))
+    }
+
+    fn normal(
+        &mut self,
+        c: char,
+        editor: &mut TextArea,
+        mode: &mut Mode,
+        mut clipboard: Option<&mut Clipboard>,
+    ) {
+        let count = self.count.unwrap_or(1);
+
+        let changed = match c {
+            'i' => {
+                *mode = Mode::Insert;
+                true
+            }
+            'a' => {
+                editor.move_cursor(CursorMove::Forward);
+                *mode = Mode::Insert;
+                true
+            }
+            'A' => {
+                editor.move_cursor(CursorMove::End);
+                *mode = Mode::Insert;
+                true
+            }
+            'I' => {
+                let buffer = Buffer::new(editor.lines());
+                let row = editor.cursor().0;
+                jump(editor, buffer.position(buffer.first_non_blank(row)));
+                *mode = Mode::Insert;
+                true
+            }
+            'o' => {
+                editor.move_cursor(CursorMove::End);
+                editor.insert_newline();
+                *mode = Mode::Insert;
+                true
+            }
+            'O' => {
+                editor.move_cursor(CursorMove::Head);
+                editor.insert_newline();
+                editor.move_cursor(CursorMove::Up);
+                *mode = Mode::Insert;
+                true
+            }
+            'R' => {
+                *mode = Mode::Replace;
+                true
+            }
+
+            'v' => {
+                editor.cancel_selection();
+                editor.start_selection();
+                *mode = Mode::Visual;
+                false
+            }
+            'V' => {
+                self.visual_row = editor.cursor().0;
+                *mode = Mode::VisualLine;
+                self.select_lines(editor);
+                false
+            }
+
+            '"' => {
+                self.pending = Pending::Register;
+                return;
+            }
+            'g' => {
+                self.pending = Pending::G(None);
+                return;
+            }
+            'r' => {
+                self.pending = Pending::Replace(count);
+                self.count = None;
+                return;
+            }
+            'd' | 'c' | 'y' => {
+                self.pending = Pending::Operator(Self::operator(c).unwrap(), count);
+                self.count = None;
+                return;
+            }
+
+            'D' | 'C' => {
+                let operator = match c {
+                    'D' => Operator::Delete,
+                    _ => Operator::Change,
+                };
+                self.motion(
+                    Motion::LineEnd,
+                    Some((operator, 1)),
+                    editor,
+                    mode,
+                    clipboard,
+                );
+                return;
+            }
+            'x' | 'X' | 's' => {
+                let (operator, motion) = match c {
+                    'x' => (Operator::Delete, Motion::Right),
+                    'X' => (Operator::Delete, Motion::Left),
+                    _ => (Operator::Change, Motion::Right),
+                };
+                self.motion(motion, Some((operator, 1)), editor, mode, clipboard);
+                return;
+            }
+            'S' | 'Y' => {
+                let operator = match c {
+                    'S' => Operator::Change,
+                    _ => Operator::Yank,
+                };
+                let buffer = Buffer::new(editor.lines());
+                let row = editor.cursor().0;
+                let last = (row + count - 1).min(buffer.last_row());
+                self.apply(operator, Range::Lines(row, last), editor, mode, clipboard)
+            }
+
+            'p' | 'P' => self.paste(c == 'P', count, editor, clipboard),
+
+            'u' => {
+                for _ in 0..count {
+                    editor.undo();
+                }
+                false
+            }
+
+            // repeat the last change
+            '.' => {
+                self.keys.clear();
+                self.count = None;
+                let keys = self.last_change.clone();
+                for _ in 0..count {
+                    for key in &keys {
+                        self.handle(*key, editor, mode, clipboard.as_deref_mut());
+                    }
+                }
+                return;
+            }
+
+            '/' => {
+                self.search = Some(String::new());
+                false
+            }
+            'n' | 'N' => {
+                if let Some(query) = self.last_search.clone() {
+                    for _ in 0..count {
+                        if let Some(position) =
+                            find(editor.lines(), &query, editor.cursor(), c == 'n')
+                        {
+                            jump(editor, position);
+                        }
+                    }
+                }
+                false
+            }
+
+            c => match Motion::from_char(c) {
+                Some(motion) => {
+                    self.motion(motion, None, editor, mode, clipboard);
+                    return;
+                }
+                None => false,
+            },
+        };
+
+        self.finish(changed, mode);
+    }
+
+    fn visual(
+        &mut self,
+        c: char,
+        editor: &mut TextArea,
+        mode: &mut Mode,
+        clipboard: Option<&mut Clipboard>,
+    ) {
+        match c {
+            'v' | 'V' => {
+                let target = match c {
+                    'v' => Mode::Visual,
+                    _ => Mode::VisualLine,
+                };
+                editor.cancel_selection();
+                if *mode == target {
+                    *mode = Mode::Normal;
+                } else if target == Mode::Visual {
+                    editor.start_selection();
+                    *mode = Mode::Visual;
+                } else {
+                    self.visual_row = editor.cursor().0;
+                    *mode = Mode::VisualLine;
+                    self.select_lines(editor);
+                }
+            }
+
+            'd' | 'x' | 'c' | 's' | 'y' => {
+                let operator = match c {
+                    'd' | 'x' => Operator::Delete,
+                    'c' | 's' => Operator::Change,
+                    _ => Operator::Yank,
+                };
+                let range = self.visual_range(editor, mode);
+                editor.cancel_selection();
+                *mode = Mode::Normal;
+                let changed = self.apply(operator, range, editor, mode, clipboard);
+                self.finish(changed, mode);
+                return;
+            }
+
+            // replace the selection
+            'p' => {
+                let range = self.visual_range(editor, mode);
+                let changed = match self.fetch(editor, clipboard) {
+                    Some(register) => {
+                        let buffer = Buffer::new(editor.lines());
+                        let (start, end) = match range {
+                            Range::Chars(start, end) => (start, end),
+                            Range::Lines(first, last) => {
+                                (buffer.starts[first], buffer.line_end(last))
+                            }
+                        };
+                        select(editor, buffer.position(start), buffer.position(end));
+                        editor.cut();
+                        editor.set_yank_text(register.text.clone());
+                        editor.insert_str(register.text);
+                        true
+                    }
+                    None => false,
+                };
+                editor.cancel_selection();
+                *mode = Mode::Normal;
+                self.finish(changed, mode);
+                return;
+            }
+
+            'i' | 'a' => {
+                self.pending = Pending::TextObject(None, c == 'i');
+                return;
+            }
+            '"' => {
+                self.pending = Pending::Register;
+                return;
+            }
+            'g' => {
+                self.pending = Pending::G(None);
+                return;
+            }
+
+            c => {
+                if let Some(motion) = Motion::from_char(c) {
+                    self.motion(motion, None, editor, mode, clipboard);
+                    return;
+                }
+            }
+        }
+
+        self.finish(false, mode);
+    }
+
+    // the selection of the visual mode, the character under the cursor included
+    fn visual_range(&self, editor: &TextArea, mode: &Mode) -> Range {
+        let buffer = Buffer::new(editor.lines());
+        let cursor = editor.cursor();
+
+        match mode {
+            Mode::VisualLine => {
+                Range::Lines(self.visual_row.min(cursor.0), self.visual_row.max(cursor.0))
+            }
+            _ => {
+                let (start, end) = editor.selection_range().unwrap_or((cursor, cursor));
+                let end = (buffer.index(end) + 1).min(buffer.chars.len());
+                Range::Chars(buffer.index(start), end)
+            }
+        }
+    }
+
+    // select the lines from the start of the linewise selection to the cursor
+    fn select_lines(&self, editor: &mut TextArea) {
+        let buffer = Buffer::new(editor.lines());
+        let row = editor.cursor().0;
+        let anchor = self.visual_row.min(buffer.last_row());
+
+        if row >= anchor {
+            select(editor, (anchor, 0), buffer.position(buffer.line_end(row)));
+        } else {
+            select(editor, buffer.position(buffer.line_end(anchor)), (row, 0));
+        }
+    }
+
+    // move the cursor, or apply the operator up to where the motion goes
+    fn motion(
+        &mut self,
+        motion: Motion,
+        operator: PendingOperator,
+        editor: &mut TextArea,
+        mode: &mut Mode,
+        clipboard: Option<&mut Clipboard>,
+    ) {
+        let count = self.count.take();
+        let buffer = Buffer::new(editor.lines());
+        let cursor = editor.cursor();
+        let index = buffer.index(cursor);
+        let (row, _) = cursor;
+        let last_row = buffer.last_row();
+
+        let Some((operator, operator_count)) = operator else {
+            let repeat = count.unwrap_or(1);
+            match motion {
+                Motion::Left => (0..repeat).for_each(|_| editor.move_cursor(CursorMove::Back)),
+                Motion::Right => (0..repeat).for_each(|_| editor.move_cursor(CursorMove::Forward)),
+                Motion::Down => (0..repeat).for_each(|_| editor.move_cursor(CursorMove::Down)),
+                Motion::Up => (0..repeat).for_each(|_| editor.move_cursor(CursorMove::Up)),
+                Motion::LineStart => editor.move_cursor(CursorMove::Head),
+                Motion::LineEnd => editor.move_cursor(CursorMove::End),
+                Motion::FirstNonBlank => jump(editor, buffer.position(buffer.first_non_blank(row))),
+                Motion::Top | Motion::Bottom => {
+                    let target = match (motion, count) {
+                        (_, Some(line)) => line.saturating_sub(1).min(last_row),
+                        (Motion::Top, None) => 0,
+                        _ => last_row,
+                    };
+                    jump(editor, (target, 0));
+                }
+                Motion::WordForward(big) => {
+                    let target = (0..repeat).fold(index, |i, _| buffer.word_forward(i, big));
+                    jump(editor, buffer.position(target));
+                }
+                Motion::WordBackward(big) => {
+                    let target = (0..repeat).fold(index, |i, _| buffer.word_backward(i, big));
+                    jump(editor, buffer.position(target));
+                }
+                Motion::WordEnd(big) => {
+                    let target = (0..repeat).fold(index, |i, _| buffer.word_end(i, big));
+                    jump(editor, buffer.position(target));
+                }
+            }
+
+            if *mode == Mode::VisualLine {
+                self.select_lines(editor);
+            }
+            self.finish(false, mode);
+            return;
+        };
+
+        let repeat = operator_count.saturating_mul(count.unwrap_or(1));
+        let line_start = buffer.starts[row];
+        let line_end = buffer.line_end(row);
+
+        let range = match motion {
+            Motion::Down => Range::Lines(row, (row + repeat).min(last_row)),
+            Motion::Up => Range::Lines(row.saturating_sub(repeat), row),
+            Motion::Top | Motion::Bottom => {
+                let target = match (motion, count) {
+                    (_, Some(line)) => line.saturating_sub(1).min(last_row),
+                    (Motion::Top, None) => 0,
+                    _ => last_row,
+                };
+                Range::Lines(row.min(target), row.max(target))
+            }
+            Motion::Left => Range::Chars(index.saturating_sub(repeat).max(line_start), index),
+            Motion::Right => Range::Chars(index, (index + repeat).min(line_end)),
+            Motion::LineStart => Range::Chars(line_start, index),
+            Motion::FirstNonBlank => {
+                let first = buffer.first_non_blank(row);
+                Range::Chars(first.min(index), first.max(index))
+            }
+            Motion::LineEnd => Range::Chars(index, line_end),
+            Motion::WordBackward(big) => Range::Chars(
+                (0..repeat).fold(index, |i, _| buffer.word_backward(i, big)),
+                index,
+            ),
+            Motion::WordEnd(big) => {
+                let end = (0..repeat).fold(index, |i, _| buffer.word_end(i, big));
+                Range::Chars(index, (end + 1).min(buffer.chars.len()))
+            }
+            // `cw` changes up to the end of the word, like `ce`
+            Motion::WordForward(big)
+                if operator == Operator::Change
+                    && buffer.chars.get(index).is_some_and(|c| !c.is_whitespace()) =>
+            {
+                // on the last character of a word, only this one is changed
+                let word_end = buffer.chars.get(index + 1).map(|c| class(*c, big))
+                    != buffer.chars.get(index).map(|c| class(*c, big));
+                let end = (0..repeat)
+                    .skip(usize::from(word_end))
+                    .fold(index, |i, _| buffer.word_end(i, big));
+                Range::Chars(index, (end + 1).min(buffer.chars.len()))
+            }
+            Motion::WordForward(big) => {
+                let mut end = (0..repeat).fold(index, |i, _| buffer.word_forward(i, big));
+                // the words of the last line stop at its end
+                if end > line_end && buffer.position(end).1 == 0 {
+                    end -= 1;
+                }
+                Range::Chars(index, end)
+            }
+        };
+
+        let changed = self.apply(operator, range, editor, mode, clipboard);
+        self.finish(changed, mode);
+    }
+
+    fn apply(
+        &mut self,
+        operator: Operator,
+        range: Range,
+        editor: &mut TextArea,
+        mode: &mut Mode,
+        clipboard: Option<&mut Clipboard>,
+    ) -> bool {
+        let buffer = Buffer::new(editor.lines());
+
+        let (start, end, register) = match range {
+            Range::Chars(start, end) => (
+                start,
+                end,
+                Register {
+                    text: buffer.chars[start..end].iter().collect(),
+                    linewise: false,
+                },
+            ),
+            Range::Lines(first, last) => {
+                let register = Register {
+                    text: editor.lines()[first..=last].join("\n"),
+                    linewise: true,
+                };
+                // the changed lines are replaced by an empty one
+                let (start, end) = if operator != Operator::Delete {
+                    (buffer.starts[first], buffer.line_end(last))
+                } else if last < buffer.last_row() {
+                    (buffer.starts[first], buffer.starts[last + 1])
+                } else if first > 0 {
+                    (buffer.starts[first] - 1, buffer.line_end(last))
+                } else {
+                    (0, buffer.line_end(last))
+                };
+                (start, end, register)
+            }
+        };
+
+        match operator {
+            Operator::Yank => {
+                self.store(register, true, editor, clipboard);
+                if let Range::Chars(..) = range {
+                    jump(editor, buffer.position(start));
+                }
+                false
+            }
+
+            Operator::Delete | Operator::Change => {
+                if end > start {
+                    select(editor, buffer.position(start), buffer.position(end));
+                    editor.cut();
+                    self.store(register, false, editor, clipboard);
+                }
+
+                if let (Range::Lines(first, _), Operator::Delete) = (range, operator) {
+                    let buffer = Buffer::new(editor.lines());
+                    let row = first.min(buffer.last_row());
+                    jump(editor, buffer.position(buffer.first_non_blank(row)));
+                }
+
+                if operator == Operator::Change {
+                    *mode = Mode::Insert;
+                }
+                end > start || operator == Operator::Change
+            }
+        }
+    }
+
+    // the deleted or yanked text goes to the unnamed register, and to the chosen one
+    fn store(
+        &mut self,
+        register: Register,
+        yank: bool,
+        editor: &mut TextArea,
+        clipboard: Option<&mut Clipboard>,
+    ) {
+        let mut clipboard = clipboard;
+
+        match self.register.take() {
+            Some('+') => {
+                if let Some(clipboard) = clipboard.as_deref_mut() {
+                    let _ = clipboard.set_text(register.text.clone());
+                }
+            }
+            Some(name @ 'a'..='z') => {
+                self.registers.insert(name, register.clone());
+            }
+            // an uppercase name appends to the register
+            Some(name @ 'A'..='Z') => {
+                let named = self.registers.entry(name.to_ascii_lowercase()).or_default();
+                if named.linewise || register.linewise {
+                    if !named.text.is_empty() {
+                        named.text.push('\n');
+                    }
+                    named.linewise = true;
+                }
+                named.text.push_str(&register.text);
+            }
+            _ => {}
+        }
+
+        // the yanked text is also copied to the clipboard
+        if yank {
+            if let Some(clipboard) = clipboard {
+                let _ = clipboard.set_text(register.text.clone());
+            }
+        }
+
+        editor.set_yank_text(register.text.clone());
+        self.unnamed = register;
+    }
+
+    fn fetch(&mut self, editor: &TextArea, clipboard: Option<&mut Clipboard>) -> Option<Register> {
+        let from_clipboard = |clipboard: Option<&mut Clipboard>| {
+            clipboard
+                .and_then(|clipboard| clipboard.get_text())
+                .map(|text| Register {
+                    text,
+                    linewise: false,
+                })
+        };
+
+        match self.register.take() {
+            Some('+') => from_clipboard(clipboard),
+            Some(name) if name.is_ascii_alphabetic() => {
+                self.registers.get(&name.to_ascii_lowercase()).cloned()
+            }
+            // the yanked text first, then the clipboard
+            _ => {
+                let text = editor.yank_text();
+                if text.is_empty() {
+                    from_clipboard(clipboard)
+                } else if text == self.unnamed.text {
+                    Some(self.unnamed.clone())
+                } else {
+                    Some(Register {
+                        text,
+                        linewise: false,
+                    })
+                }
+            }
+        }
+    }
+
+    fn paste(
+        &mut self,
+        before: bool,
+        count: usize,
+        editor: &mut TextArea,
+        clipboard: Option<&mut Clipboard>,
+    ) -> bool {
+        let Some(register) = self.fetch(editor, clipboard) else {
+            return false;
+        };
+
+        let (row, col) = editor.cursor();
+
+        if register.linewise {
+            let text = vec![register.text; count].join("\n");
+            if before {
+                jump(editor, (row, 0));
+                editor.insert_str(format!("{}\n", text));
+                jump(editor, (row, 0));
+            } else {
+                editor.move_cursor(CursorMove::End);
+                editor.insert_str(format!("\n{}", text));
+                jump(editor, (row + 1, 0));
+            }
+        } else {
+            if !before && col < editor.lines()[row].chars().count() {
+                editor.move_cursor(CursorMove::Forward);
+            }
+            editor.insert_str(register.text.repeat(count));
+        }
+
+        true
+    }
+
+    fn replace_chars(&mut self, c: char, count: usize, editor: &mut TextArea) -> bool {
+        let (row, col) = editor.cursor();
+        if col + count > editor.lines()[row].chars().count() {
+            return false;
+        }
+
+        for _ in 0..count {
+            editor.delete_next_char();
+            editor.insert_char(c);
+        }
+        editor.move_cursor(CursorMove::Back);
+        true
+    }
+
+    fn handle_insert(&mut self, key_event: KeyEvent, editor: &mut TextArea, mode: &mut Mode) {
+        if self.inserting {
+            self.keys.push(key_event);
+        }
+
+        let text = !key_event
+            .modifiers
+            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+
+        match key_event.code {
+            KeyCode::Enter => {
+                editor.insert_newline();
+            }
+
+            KeyCode::Char(c) if text => {
+                // `R` types over the text
+                if *mode == Mode::Replace {
+                    let (row, col) = editor.cursor();
+                    if col < editor.lines()[row].chars().count() {
+                        editor.delete_next_char();
+                    }
+                }
+                editor.insert_char(c);
+            }
+
+            KeyCode::Backspace => match mode {
+                Mode::Replace => editor.move_cursor(CursorMove::Back),
+                _ => {
+                    editor.delete_char();
+                }
+            },
+
+            KeyCode::Esc => {
+                *mode = Mode::Normal;
+                if self.inserting {
+                    self.last_change = std::mem::take(&mut self.keys);
+                    self.inserting = false;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_search(&mut self, key_event: KeyEvent, editor: &mut TextArea) {
+        let Some(query) = self.search.as_mut() else {
+            return;
+        };
+
+        match key_event.code {
+            KeyCode::Char(c) => query.push(c),
+            // deleting before the start of the query cancels the search
+            KeyCode::Backspace if query.is_empty() => self.search = None,
+            KeyCode::Backspace => {
+                query.pop();
+            }
+            KeyCode::Enter => {
+                if !query.is_empty() {
+                    if let Some(position) = find(editor.lines(), query, editor.cursor(), true) {
+                        jump(editor, position);
+                    }
+                    self.last_search = Some(query.clone());
+                }
+                self.search = None;
+            }
+            KeyCode::Esc => self.search = None,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Prompt {
+        vim: Vim,
+        editor: TextArea<'static>,
+        mode: Mode,
+    }
+
+    impl Prompt {
+        fn new(text: &str, cursor: (usize, usize)) -> Self {
+            let mut editor = TextArea::from(text.split('\n'));
+            jump(&mut editor, cursor);
+            Self {
+                vim: Vim::new(),
+                editor,
+                mode: Mode::Normal,
+            }
+        }
+
+        // `<esc>` and `<c-r>` are the escape key and ctrl + r
+        fn keys(&mut self, keys: &str) -> &mut Self {
+            let mut keys = keys;
+            while let Some(c) = keys.chars().next() {
+                let (key, length) = if keys.starts_with("<esc>") {
+                    (KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE), 5)
+                } else if keys.starts_with("<c-r>") {
+                    (KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL), 5)
+                } else {
+                    (
+                        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE),
+                        c.len_utf8(),
+                    )
+                };
+                self.vim.handle(key, &mut self.editor, &mut self.mode, None);
+                keys = &keys[length..];
+            }
+            self
+        }
+
+        fn assert(&self, text: &str, cursor: (usize, usize)) {
+            assert_eq!(self.editor.lines().join("\n"), text);
+            assert_eq!(self.editor.cursor(), cursor);
+        }
+    }
+
+    #[test]
+    fn counts() {
+        Prompt::new("a\nb\nc\nd\ne", (1, 0))
+            .keys("3dd")
+            .assert("a\ne", (1, 0));
+        Prompt::new("1\n2\n3\n4\n5\n6\n7", (0, 0))
+            .keys("5j")
+            .assert("1\n2\n3\n4\n5\n6\n7", (5, 0));
+        Prompt::new("one two three four five", (0, 0))
+            .keys("2d2w")
+            .assert("five", (0, 0));
+    }
+
+    #[test]
+    fn text_objects() {
+        let mut prompt = Prompt::new("foo bar baz", (0, 5));
+        prompt.keys("ciwx");
+        assert_eq!(prompt.mode, Mode::Insert);
+        prompt.keys("<esc>").assert("foo x baz", (0, 5));
+
+        Prompt::new("say \"hello world\" now", (0, 7))
+            .keys("di\"")
+            .assert("say \"\" now", (0, 5));
+        Prompt::new("f(a, (b)) + 1", (0, 3))
+            .keys("da(")
+            .assert("f + 1", (0, 1));
+    }
+
+    #[test]
+    fn named_registers() {
+        let mut prompt = Prompt::new("one\ntwo\nthree", (0, 0));
+        prompt.keys("\"ayyj\"byy");
+        // the unnamed register is the last yank, the named ones are kept
+        prompt.keys("Gp").assert("one\ntwo\nthree\ntwo", (3, 0));
+        prompt
+            .keys("\"ap")
+            .assert("one\ntwo\nthree\ntwo\none", (4, 0));
+
+        // an uppercase name appends to the register
+        prompt.keys("gg\"Ayy\"aP");
+        prompt.assert("one\none\none\ntwo\nthree\ntwo\none", (0, 0));
+    }
+
+    #[test]
+    fn repeat_the_last_change() {
+        Prompt::new("one two three four five", (0, 0))
+            .keys("dw.")
+            .assert("three four five", (0, 0));
+        Prompt::new("one two three four five", (0, 0))
+            .keys("dw2.")
+            .assert("four five", (0, 0));
+
+        // the text typed after a change is repeated with it
+        Prompt::new("one two three", (0, 0))
+            .keys("ciwX<esc>w.")
+            .assert("X X three", (0, 3));
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut prompt = Prompt::new("abc", (0, 0));
+        prompt.keys("xxuu");
+        assert_eq!(prompt.editor.lines(), ["abc"]);
+
+        prompt.keys("<c-r>").assert("bc", (0, 0));
+        prompt.keys("u2<c-r>").assert("c", (0, 0));
+        // nothing left to redo
+        prompt.keys("<c-r>").assert("c", (0, 0));
+    }
+
+    #[test]
+    fn replace() {
+        Prompt::new("abcd", (0, 0))
+            .keys("3rx")
+            .assert("xxxd", (0, 2));
+        // not enough characters to replace
+        Prompt::new("abcd", (0, 0))
+            .keys("5rx")
+            .assert("abcd", (0, 0));
+
+        Prompt::new("abcd", (0, 1))
+            .keys("RXY<esc>")
+            .assert("aXYd", (0, 3));
+        Prompt::new("ab", (0, 1))
+            .keys("Rxyz<esc>")
+            .assert("axyz", (0, 4));
+    }
+
+    #[test]
+    fn word_ends() {
+        let mut prompt = Prompt::new("foo.bar baz", (0, 0));
+        prompt.keys("e").assert("foo.bar baz", (0, 2));
+        prompt.keys("e").assert("foo.bar baz", (0, 3));
+        prompt.keys("E").assert("foo.bar baz", (0, 6));
+        prompt.keys("2E").assert("foo.bar baz", (0, 10));
+
+        Prompt::new("foo.bar baz", (0, 0))
+            .keys("de")
+            .assert(".bar baz", (0, 0));
+        Prompt::new("foo.bar baz", (0, 0))
+            .keys("dE")
+            .assert(" baz", (0, 0));
+    }
+
+    #[test]
+    fn linewise_visual_mode() {
+        let mut prompt = Prompt::new("a\nb\nc\nd", (1, 0));
+        prompt.keys("Vj");
+        assert_eq!(prompt.mode, Mode::VisualLine);
+        prompt.keys("d").assert("a\nd", (1, 0));
+        assert_eq!(prompt.mode, Mode::Normal);
+
+        // the selection goes up from the line where it started
+        Prompt::new("a\nb\nc\nd", (2, 0))
+            .keys("VkyggP")
+            .assert("b\nc\na\nb\nc\nd", (0, 0));
+    }
+}